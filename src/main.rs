@@ -1,68 +1,348 @@
 use anyhow::Result;
+use cassowary::strength::{REQUIRED, STRONG, WEAK};
+use cassowary::WeightedRelation::{EQ, GE};
+use cassowary::{Constraint, Expression, Solver, Variable};
 use petgraph::graph::{DiGraph, NodeIndex};
 use std::any::Any;
 use std::borrow::BorrowMut;
 use std::cell::RefCell;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::num::NonZeroUsize;
+use std::rc::Rc;
 use std::sync::Arc;
-use vello::kurbo::{Affine, Point, Rect, RoundedRect, Size, Stroke};
+use vello::kurbo::{
+    Affine, BezPath, Insets, Line, Point, Rect, RoundedRect, RoundedRectRadii, Shape, Size, Stroke,
+    Vec2,
+};
 use vello::peniko::color::palette;
-use vello::peniko::Color;
+use vello::peniko::{BlendMode, Brush, Color, Gradient, Mix};
 use vello::util::{RenderContext, RenderSurface};
 use vello::wgpu;
 use vello::{AaConfig, Renderer, RendererOptions, Scene};
 use winit::application::ApplicationHandler;
 use winit::dpi::LogicalSize;
-use winit::event::{ElementState, WindowEvent};
+use winit::event::{ElementState, MouseButton, MouseScrollDelta, WindowEvent};
 use winit::event_loop::{ActiveEventLoop, EventLoop};
+use winit::keyboard::{KeyCode, ModifiersState, PhysicalKey};
 use winit::window::Window;
 
-#[derive(Debug)]
 pub struct ActiveRenderState<'s> {
     surface: RenderSurface<'s>,
+    frame: FrameTextures,
     window: Arc<Window>,
 }
 
+/// What's been rendered to the window so far. Only the damaged parts of a frame are rendered, so
+/// the rest is kept from the frames before and the whole thing is copied to the surface.
+struct FrameTextures {
+    /// Vello renders into this, which is then copied into `presented` where it was damaged
+    rendered: wgpu::Texture,
+    rendered_view: wgpu::TextureView,
+    presented: wgpu::Texture,
+    presented_view: wgpu::TextureView,
+    blitter: wgpu::util::TextureBlitter,
+}
+
+impl FrameTextures {
+    fn new(device: &wgpu::Device, surface: &RenderSurface<'_>) -> Self {
+        let texture = |usage| {
+            device.create_texture(&wgpu::TextureDescriptor {
+                label: None,
+                size: wgpu::Extent3d {
+                    width: surface.config.width.max(1),
+                    height: surface.config.height.max(1),
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: wgpu::TextureFormat::Rgba8Unorm,
+                usage,
+                view_formats: &[],
+            })
+        };
+        let rendered =
+            texture(wgpu::TextureUsages::STORAGE_BINDING | wgpu::TextureUsages::COPY_SRC);
+        let presented =
+            texture(wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST);
+
+        Self {
+            rendered_view: rendered.create_view(&wgpu::TextureViewDescriptor::default()),
+            rendered,
+            presented_view: presented.create_view(&wgpu::TextureViewDescriptor::default()),
+            presented,
+            blitter: wgpu::util::TextureBlitter::new(device, surface.format),
+        }
+    }
+}
+
 enum RenderState<'s> {
     Active(ActiveRenderState<'s>),
     Suspended(Option<Arc<Window>>),
 }
 
+/// How far one notch of a scroll wheel scrolls, in logical pixels
+const SCROLL_LINE_HEIGHT: f64 = 40.0;
+
 struct SimpleVelloApp<'s> {
     context: RenderContext,
     renderers: Vec<Option<Renderer>>,
     state: RenderState<'s>,
     scene: Scene,
+    /// `scene` clipped to what's damaged, which is all that's rendered
+    damaged_scene: Scene,
     widget_tree: &'static WidgetTree,
+    modifiers: ModifiersState,
+    cursor_position: Point,
 }
 
-#[derive(Clone, Copy, Debug, PartialEq)]
+#[derive(Clone, Copy, Debug)]
 struct Constraints {
     min: Size,
     max: Size,
 }
 
 impl Constraints {
+    /// Only `size` is allowed
+    fn tight(size: Size) -> Self {
+        Constraints {
+            min: size,
+            max: size,
+        }
+    }
+
+    /// Anything from zero up to `size` is allowed
+    fn loose(size: Size) -> Self {
+        Constraints {
+            min: Size::ZERO,
+            max: size,
+        }
+    }
+
+    /// Tight to the given dimensions, and as big as possible in the others. Meant to be
+    /// `enforce`d against the parent's constraints, which brings the infinities back down.
+    fn expand(width: Option<f64>, height: Option<f64>) -> Self {
+        Self::tight(Size::new(
+            width.unwrap_or(f64::INFINITY),
+            height.unwrap_or(f64::INFINITY),
+        ))
+    }
+
+    /// Tight to the given dimensions, and anything from zero to infinity in the others
+    fn tight_for(width: Option<f64>, height: Option<f64>) -> Self {
+        Self::loose(Size::new(f64::INFINITY, f64::INFINITY)).tighten(width, height)
+    }
+
+    /// Lets the child be any size along `axis`, keeping the limits across it
+    fn unbounded(&self, axis: Axis) -> Self {
+        Constraints {
+            min: axis.size(0.0, axis.cross(self.min)),
+            max: axis.size(f64::INFINITY, axis.cross(self.max)),
+        }
+    }
+
+    /// Shrinks both bounds by `insets`, never going below zero or letting `max` drop below `min`
+    fn deflate(&self, insets: Insets) -> Self {
+        let horizontal = insets.x_value();
+        let vertical = insets.y_value();
+
+        let min = Size::new(
+            (self.min.width - horizontal).max(0.0),
+            (self.min.height - vertical).max(0.0),
+        );
+        let max = Size::new(
+            (self.max.width - horizontal).max(min.width),
+            (self.max.height - vertical).max(min.height),
+        );
+
+        Constraints { min, max }
+    }
+
+    /// These constraints, squeezed into the range allowed by `other`
+    fn enforce(&self, other: Constraints) -> Self {
+        Constraints {
+            min: other.clamp_size(self.min),
+            max: other.clamp_size(self.max),
+        }
+    }
+
+    /// Pins the given dimensions, as close to the requested value as these constraints allow
+    fn tighten(&self, width: Option<f64>, height: Option<f64>) -> Self {
+        let mut tightened = *self;
+        if let Some(width) = width {
+            tightened.min.width = self.constrain_width(width);
+            tightened.max.width = tightened.min.width;
+        }
+        if let Some(height) = height {
+            tightened.min.height = self.constrain_height(height);
+            tightened.max.height = tightened.min.height;
+        }
+        tightened
+    }
+
+    /// Keeps the maximum, but drops the minimum to zero
+    fn loosen(&self) -> Self {
+        Self::loose(self.max)
+    }
+
+    fn is_tight(&self) -> bool {
+        self.min.width >= self.max.width && self.min.height >= self.max.height
+    }
+
+    fn has_bounded_width(&self) -> bool {
+        self.max.width.is_finite()
+    }
+
+    fn has_bounded_height(&self) -> bool {
+        self.max.height.is_finite()
+    }
+
+    /// Makes sense of constraints that don't describe any size: `min` can't be negative (or NaN),
+    /// and `max` can't be below `min`
+    fn normalize(&self) -> Self {
+        let min = Size::new(self.min.width.max(0.0), self.min.height.max(0.0));
+        let max = Size::new(
+            self.max.width.max(min.width),
+            self.max.height.max(min.height),
+        );
+        Constraints { min, max }
+    }
+
+    fn constrain_width(&self, width: f64) -> f64 {
+        width.max(self.min.width).min(self.max.width)
+    }
+
+    fn constrain_height(&self, height: f64) -> f64 {
+        height.max(self.min.height).min(self.max.height)
+    }
+
     fn clamp_size(&self, s: Size) -> Size {
         Size {
-            width: s.width.max(self.min.width).min(self.max.width),
-            height: s.height.max(self.min.height).min(self.max.height),
+            width: self.constrain_width(s.width),
+            height: self.constrain_height(s.height),
+        }
+    }
+
+    /// The smallest size that fits
+    fn smallest(&self) -> Size {
+        self.min
+    }
+
+    /// Like `clamp_size`, but scales both dimensions together so that `size`'s aspect ratio
+    /// survives wherever the constraints allow it
+    fn constrain_aspect(&self, size: Size) -> Size {
+        if self.is_tight() || size.width <= 0.0 || size.height <= 0.0 {
+            return self.clamp_size(size);
+        }
+
+        let ratio = size.width / size.height;
+        let Size {
+            mut width,
+            mut height,
+        } = size;
+
+        if width > self.max.width {
+            width = self.max.width;
+            height = width / ratio;
+        }
+        if height > self.max.height {
+            height = self.max.height;
+            width = height * ratio;
+        }
+        if width < self.min.width {
+            width = self.min.width;
+            height = width / ratio;
+        }
+        if height < self.min.height {
+            height = self.min.height;
+            width = height * ratio;
         }
+
+        self.clamp_size(Size::new(width, height))
+    }
+
+    fn canonical(&self) -> [u64; 4] {
+        [
+            canonical_bits(self.min.width),
+            canonical_bits(self.min.height),
+            canonical_bits(self.max.width),
+            canonical_bits(self.max.height),
+        ]
+    }
+}
+
+/// Bits that are equal exactly when the values should be considered equal, so `0.0` and `-0.0`
+/// (and every NaN) agree with each other. Floats in query keys are hashed and compared by these.
+fn canonical_bits(value: f64) -> u64 {
+    if value == 0.0 {
+        0.0f64.to_bits()
+    } else if value.is_nan() {
+        f64::NAN.to_bits()
+    } else {
+        value.to_bits()
     }
 }
 
 impl std::hash::Hash for Constraints {
     fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
-        self.min.width.to_bits().hash(state);
-        self.min.height.to_bits().hash(state);
-        self.max.width.to_bits().hash(state);
-        self.max.height.to_bits().hash(state);
+        self.canonical().hash(state);
+    }
+}
+
+impl std::cmp::PartialEq for Constraints {
+    fn eq(&self, other: &Self) -> bool {
+        self.canonical() == other.canonical()
     }
 }
 
 impl std::cmp::Eq for Constraints {}
 
+/// Which way text, and anything laid out along with it, flows horizontally
+#[derive(Clone, Copy, Hash, Debug, Default, PartialEq, Eq)]
+enum TextDirection {
+    #[default]
+    Ltr,
+    Rtl,
+}
+
+#[derive(Clone, Copy, Hash, Debug, PartialEq, Eq)]
+enum Axis {
+    Horizontal,
+    Vertical,
+}
+
+impl Axis {
+    /// The extent of `size` along this axis
+    fn main(&self, size: Size) -> f64 {
+        match self {
+            Axis::Horizontal => size.width,
+            Axis::Vertical => size.height,
+        }
+    }
+
+    /// The extent of `size` across this axis
+    fn cross(&self, size: Size) -> f64 {
+        match self {
+            Axis::Horizontal => size.height,
+            Axis::Vertical => size.width,
+        }
+    }
+
+    fn size(&self, main: f64, cross: f64) -> Size {
+        match self {
+            Axis::Horizontal => Size::new(main, cross),
+            Axis::Vertical => Size::new(cross, main),
+        }
+    }
+
+    fn point(&self, main: f64, cross: f64) -> Point {
+        match self {
+            Axis::Horizontal => Point::new(main, cross),
+            Axis::Vertical => Point::new(cross, main),
+        }
+    }
+}
+
 #[derive(Clone, Copy, Hash, Debug, PartialEq, Eq)]
 struct LayouterConstrainChildrenCtx {
     child_n: usize,
@@ -80,28 +360,203 @@ struct LayouterSizeSelfCtx {
     constraints: Constraints,
 }
 
+/// The defaults for `constraints_for_child`, `position_for_child` and `size_for_self` pass
+/// everything through to a single child, which is all most wrappers need.
 trait Layouter {
+    /// By default, our own constraints
     fn constraints_for_child(
         &self,
-        tree: &'static WidgetTree,
-        index: NodeIndex,
+        _tree: &'static WidgetTree,
+        _index: NodeIndex,
         ctx: LayouterConstrainChildrenCtx,
-    ) -> Constraints;
+    ) -> Constraints {
+        ctx.self_constraints
+    }
+
+    /// By default, our origin
     fn position_for_child(
         &self,
-        tree: &'static WidgetTree,
-        index: NodeIndex,
-        ctx: LayoutChildWasSizedCtx,
-    ) -> Point;
+        _tree: &'static WidgetTree,
+        _index: NodeIndex,
+        _ctx: LayoutChildWasSizedCtx,
+    ) -> Point {
+        Point::ORIGIN
+    }
+
+    /// By default, our first child's size, or the smallest size allowed if there isn't one
     fn size_for_self(
         &self,
         tree: &'static WidgetTree,
         index: NodeIndex,
         ctx: LayouterSizeSelfCtx,
-    ) -> Size;
+    ) -> Size {
+        match tree.children(index).first() {
+            Some(&c) => ctx.constraints.clamp_size(tree.query_node_size(c)),
+            None => ctx.constraints.min,
+        }
+    }
+
+    /// The smallest width this node can be without its content overflowing, if it were
+    /// `height` tall. By default, the largest of its children's.
+    fn min_intrinsic_width(&self, tree: &'static WidgetTree, index: NodeIndex, height: f64) -> f64 {
+        tree.children(index)
+            .into_iter()
+            .map(|c| tree.query_min_intrinsic_width(c, height))
+            .fold(0.0, f64::max)
+    }
+
+    /// The width beyond which growing this node wouldn't make it any shorter, if it were
+    /// `height` tall. By default, the largest of its children's.
+    fn max_intrinsic_width(&self, tree: &'static WidgetTree, index: NodeIndex, height: f64) -> f64 {
+        tree.children(index)
+            .into_iter()
+            .map(|c| tree.query_max_intrinsic_width(c, height))
+            .fold(0.0, f64::max)
+    }
+
+    /// The smallest height this node can be without its content overflowing, if it were
+    /// `width` wide. By default, the largest of its children's.
+    fn min_intrinsic_height(&self, tree: &'static WidgetTree, index: NodeIndex, width: f64) -> f64 {
+        tree.children(index)
+            .into_iter()
+            .map(|c| tree.query_min_intrinsic_height(c, width))
+            .fold(0.0, f64::max)
+    }
+
+    /// The height beyond which growing this node wouldn't make it any narrower, if it were
+    /// `width` wide. By default, the largest of its children's.
+    fn max_intrinsic_height(&self, tree: &'static WidgetTree, index: NodeIndex, width: f64) -> f64 {
+        tree.children(index)
+            .into_iter()
+            .map(|c| tree.query_max_intrinsic_height(c, width))
+            .fold(0.0, f64::max)
+    }
+
+    /// Distance from the top of this node to its first baseline, if it has one. By default, the
+    /// baseline of the first child that has one, offset by where that child was placed.
+    fn baseline(&self, tree: &'static WidgetTree, index: NodeIndex) -> Option<f64> {
+        tree.children(index).into_iter().find_map(|c| {
+            let child_baseline = tree.query_node_baseline(c)?;
+            Some(tree.query_node_position(c).y + child_baseline)
+        })
+    }
+
+    /// Takes the measurement `key` for `query_layout_measure`. Only layouters that ask for their
+    /// own measurements need to implement this, so by default there's nothing to measure.
+    fn measure(&self, _tree: &'static WidgetTree, _index: NodeIndex, _key: usize) -> f64 {
+        0.0
+    }
+
+    /// Extra information a parent layouter can read off this node, e.g. `Positioned` insets
+    /// for a `Stack`
+    fn parent_data(&self) -> Option<&dyn Any> {
+        None
+    }
+
+    /// The layer this node's children are drawn into, if any. The node's own drawer draws
+    /// outside of it, underneath.
+    fn layer(&self, _tree: &'static WidgetTree, _index: NodeIndex) -> Option<Layer> {
+        None
+    }
+
+    /// Whether this node's subtree is drawn into a scene of its own, which is reused in frames
+    /// where nothing in the subtree changed, instead of visiting the subtree again. The scene is
+    /// put together again whenever anything in it changes, including where the node is.
+    fn is_repaint_boundary(&self) -> bool {
+        false
+    }
+
+    /// Applied to this node's drawing and its whole subtree, relative to the node's origin
+    fn transform(&self, _tree: &'static WidgetTree, _index: NodeIndex) -> Affine {
+        Affine::IDENTITY
+    }
+
+    /// Called before each frame is drawn, outside of any query, so layouters that manage their
+    /// own children can add, remove or rebind them
+    fn update(&self, _tree: &'static WidgetTree, _index: NodeIndex) {}
+
+    /// Handles a pointer event, returning whether it was handled. Unhandled events bubble up to
+    /// the parent. A node that handles `Down` captures the pointer, and gets every event until
+    /// the matching `Up` regardless of where the pointer is.
+    fn on_pointer(
+        &self,
+        _tree: &'static WidgetTree,
+        _index: NodeIndex,
+        _event: PointerEvent,
+    ) -> bool {
+        false
+    }
+
+    /// Handles a scroll wheel or touchpad scroll of `delta`, in this node's coordinates, returning
+    /// whether it was handled. Positive deltas move towards the end of the content. Unhandled
+    /// scrolls bubble up to the parent.
+    fn on_scroll(&self, _tree: &'static WidgetTree, _index: NodeIndex, _delta: Vec2) -> bool {
+        false
+    }
+
+    /// What this layouter is called in the debug overlay
+    fn debug_name(&self) -> &'static str {
+        let name = std::any::type_name::<Self>();
+        name.rsplit("::").next().unwrap_or(name)
+    }
+
+    /// Space between this node's edges and its children, shaded in the debug overlay
+    fn debug_padding(&self, _tree: &'static WidgetTree, _index: NodeIndex) -> Insets {
+        Insets::ZERO
+    }
 }
 
-struct RowLayouter {}
+#[derive(Default)]
+struct RowLayouter {
+    cross_axis_alignment: CrossAxisAlignment,
+}
+
+impl RowLayouter {
+    /// Total width of the children before `child_n`, built up from the previous child's so each
+    /// child only adds its own width. Constraints use this rather than the previous child's
+    /// position, because in right-to-left rows positions depend on the row's width.
+    fn leading_width(&self, tree: &'static WidgetTree, index: NodeIndex, child_n: usize) -> f64 {
+        tree.query_layout_measure(LayoutMeasure {
+            index,
+            key: child_n,
+        })
+    }
+
+    /// Vertical offset of `child` within the row. This can't ask for the row's own size, since
+    /// that depends on where the children were placed.
+    fn cross_offset(&self, tree: &'static WidgetTree, index: NodeIndex, child: NodeIndex) -> f64 {
+        let children = tree.children(index);
+
+        if self.cross_axis_alignment == CrossAxisAlignment::Baseline {
+            let Some(child_baseline) = tree.query_node_baseline(child) else {
+                return 0.0;
+            };
+
+            let row_baseline = children
+                .into_iter()
+                .filter_map(|c| tree.query_node_baseline(c))
+                .fold(0.0, f64::max);
+
+            return row_baseline - child_baseline;
+        }
+
+        if self.cross_axis_alignment == CrossAxisAlignment::Start {
+            return 0.0;
+        }
+
+        let tallest_child_height = children
+            .into_iter()
+            .map(|c| tree.query_node_size(c).height)
+            .fold(0.0, f64::max);
+        let row_height = tree
+            .query_node_constraints(index)
+            .clamp_size(Size::new(0.0, tallest_child_height))
+            .height;
+
+        let child_height = tree.query_node_size(child).height;
+        self.cross_axis_alignment.offset(row_height - child_height)
+    }
+}
 
 impl Layouter for RowLayouter {
     fn constraints_for_child(
@@ -114,24 +569,13 @@ impl Layouter for RowLayouter {
             return ctx.self_constraints;
         }
 
-        let prev_child_index = tree.query_nth_child(NthChild {
-            parent_index: index,
-            child_n: ctx.child_n - 1,
-        });
-
-        let prev_child_size = tree.query_node_size(prev_child_index);
-        let prev_child_position = tree.query_node_position(prev_child_index);
-
         let remaining_width =
-            ctx.self_constraints.max.width - prev_child_position.x - prev_child_size.width;
+            ctx.self_constraints.max.width - self.leading_width(tree, index, ctx.child_n);
 
-        Constraints {
-            min: ctx.self_constraints.min,
-            max: Size {
-                width: remaining_width,
-                height: ctx.self_constraints.max.height,
-            },
-        }
+        ctx.self_constraints.enforce(Constraints::loose(Size::new(
+            remaining_width.max(0.0),
+            f64::INFINITY,
+        )))
     }
 
     fn position_for_child(
@@ -140,22 +584,36 @@ impl Layouter for RowLayouter {
         index: NodeIndex,
         ctx: LayoutChildWasSizedCtx,
     ) -> Point {
-        if ctx.child_n == 0 {
-            return Point::ORIGIN;
-        }
-
-        let prev_child_index = tree.query_nth_child(NthChild {
+        let child_index = tree.query_nth_child(NthChild {
             parent_index: index,
-            child_n: ctx.child_n - 1,
+            child_n: ctx.child_n,
         });
+        let y = self.cross_offset(tree, index, child_index);
+
+        // Each child starts where the previous one ended: after it left to right, or before it
+        // right to left, where the first child sits against the row's right edge
+        let direction = tree.text_direction();
+        let x = if ctx.child_n == 0 {
+            match direction {
+                TextDirection::Ltr => 0.0,
+                TextDirection::Rtl => {
+                    tree.query_node_size(index).width - tree.query_node_size(child_index).width
+                }
+            }
+        } else {
+            let prev_child_index = tree.query_nth_child(NthChild {
+                parent_index: index,
+                child_n: ctx.child_n - 1,
+            });
+            let prev_child_x = tree.query_node_position(prev_child_index).x;
 
-        let prev_child_size = tree.query_node_size(prev_child_index);
-        let prev_child_position = tree.query_node_position(prev_child_index);
+            match direction {
+                TextDirection::Ltr => prev_child_x + tree.query_node_size(prev_child_index).width,
+                TextDirection::Rtl => prev_child_x - tree.query_node_size(child_index).width,
+            }
+        };
 
-        Point::new(
-            prev_child_position.x + prev_child_size.width,
-            prev_child_position.y,
-        )
+        Point::new(x, y)
     }
 
     fn size_for_self(
@@ -164,29 +622,66 @@ impl Layouter for RowLayouter {
         index: NodeIndex,
         ctx: LayouterSizeSelfCtx,
     ) -> Size {
-        let child_indices = tree
-            .tree
-            .borrow()
-            .neighbors_directed(index, petgraph::Direction::Outgoing)
-            .collect::<Vec<_>>();
-        let last_child_index = *child_indices.last().unwrap();
+        let child_indices = tree.children(index);
+
+        let width = child_indices
+            .iter()
+            .map(|&c| tree.query_node_size(c).width)
+            .sum();
+
+        // Baseline alignment can push children down, so the row has to fit the lowest bottom
+        // edge rather than just the tallest child
+        let height = child_indices
+            .iter()
+            .map(|&c| {
+                let offset = match self.cross_axis_alignment {
+                    CrossAxisAlignment::Baseline => self.cross_offset(tree, index, c),
+                    _ => 0.0,
+                };
+                offset + tree.query_node_size(c).height
+            })
+            .fold(0.0, f64::max);
 
-        let last_child_x = tree.query_node_position(last_child_index).x;
+        return ctx.constraints.clamp_size(Size { width, height });
+    }
 
-        let last_child_width = tree.query_node_size(last_child_index).width;
+    /// The width of the children before child `key`
+    fn measure(&self, tree: &'static WidgetTree, index: NodeIndex, key: usize) -> f64 {
+        if key == 0 {
+            return 0.0;
+        }
 
-        return Size {
-            width: last_child_x + last_child_width,
-            height: ctx.constraints.max.height, // todo(chad): only need to be as tall as our tallest child
-        };
+        let prev_child_index = tree.query_nth_child(NthChild {
+            parent_index: index,
+            child_n: key - 1,
+        });
+        self.leading_width(tree, index, key - 1) + tree.query_node_size(prev_child_index).width
+    }
+
+    fn min_intrinsic_width(&self, tree: &'static WidgetTree, index: NodeIndex, height: f64) -> f64 {
+        tree.children(index)
+            .into_iter()
+            .map(|c| tree.query_min_intrinsic_width(c, height))
+            .sum()
+    }
+
+    fn max_intrinsic_width(&self, tree: &'static WidgetTree, index: NodeIndex, height: f64) -> f64 {
+        tree.children(index)
+            .into_iter()
+            .map(|c| tree.query_max_intrinsic_width(c, height))
+            .sum()
     }
 }
 
+/// Insets its child. `start` and `end` are added to whichever of `left` and `right` they
+/// resolve to for the current `TextDirection`.
 struct Padded {
     top: f64,
     bottom: f64,
     left: f64,
     right: f64,
+    start: f64,
+    end: f64,
 }
 
 impl Padded {
@@ -196,6 +691,8 @@ impl Padded {
             bottom: p,
             left: p,
             right: p,
+            start: 0.0,
+            end: 0.0,
         };
     }
 
@@ -205,35 +702,52 @@ impl Padded {
             bottom: vertical,
             left: horizontal,
             right: horizontal,
+            start: 0.0,
+            end: 0.0,
+        };
+    }
+
+    fn directional(top: f64, start: f64, bottom: f64, end: f64) -> Self {
+        return Padded {
+            top,
+            bottom,
+            left: 0.0,
+            right: 0.0,
+            start,
+            end,
+        };
+    }
+
+    fn insets(&self, tree: &'static WidgetTree) -> Insets {
+        let (left, right) = match tree.text_direction() {
+            TextDirection::Ltr => (self.start, self.end),
+            TextDirection::Rtl => (self.end, self.start),
         };
+
+        Insets::new(self.left + left, self.top, self.right + right, self.bottom)
     }
 }
 
 impl Layouter for Padded {
     fn constraints_for_child(
         &self,
-        _tree: &'static WidgetTree,
+        tree: &'static WidgetTree,
         _index: NodeIndex,
         ctx: LayouterConstrainChildrenCtx,
     ) -> Constraints {
-        Constraints {
-            min: ctx.self_constraints.min,
-            max: Size {
-                width: ctx.self_constraints.max.width - self.left - self.right,
-                height: ctx.self_constraints.max.height - self.top - self.bottom,
-            },
-        }
+        ctx.self_constraints.deflate(self.insets(tree))
     }
 
     fn position_for_child(
         &self,
-        _tree: &'static WidgetTree,
+        tree: &'static WidgetTree,
         _index: NodeIndex,
         _ctx: LayoutChildWasSizedCtx,
     ) -> Point {
+        let insets = self.insets(tree);
         Point {
-            x: self.left,
-            y: self.top,
+            x: insets.x0,
+            y: insets.y0,
         }
     }
 
@@ -251,77 +765,306 @@ impl Layouter for Padded {
         });
 
         let first_child_size = tree.query_node_size(first_child);
+        let insets = self.insets(tree);
 
         Size {
-            width: first_child_size.width + self.left + self.right,
-            height: first_child_size.height + self.top + self.bottom,
+            width: first_child_size.width + insets.x_value(),
+            height: first_child_size.height + insets.y_value(),
         }
     }
-}
-
-struct DrawerCtx<'a> {
-    rect: Rect,
-    scene: &'a mut Scene,
-}
 
-trait Drawer {
-    fn draw(&self, ctx: DrawerCtx);
-}
+    fn min_intrinsic_width(&self, tree: &'static WidgetTree, index: NodeIndex, height: f64) -> f64 {
+        let insets = self.insets(tree);
+        let height = (height - insets.y_value()).max(0.0);
+        let child = tree
+            .children(index)
+            .first()
+            .map(|&c| tree.query_min_intrinsic_width(c, height));
+        child.unwrap_or(0.0) + insets.x_value()
+    }
 
-struct WidgetTreeWeight {
-    layouter: Box<dyn Layouter>,
-    drawer: Option<Box<dyn Drawer>>,
-}
+    fn max_intrinsic_width(&self, tree: &'static WidgetTree, index: NodeIndex, height: f64) -> f64 {
+        let insets = self.insets(tree);
+        let height = (height - insets.y_value()).max(0.0);
+        let child = tree
+            .children(index)
+            .first()
+            .map(|&c| tree.query_max_intrinsic_width(c, height));
+        child.unwrap_or(0.0) + insets.x_value()
+    }
 
-#[derive(Clone, Copy, Hash, Debug, PartialEq, Eq)]
-enum QueryDependency {
-    NodePosition(NodeIndex),
-    NodeConstraints(NodeIndex),
-    NodeSize(NodeIndex),
-    NthChild(NthChild),
-    Signal(SignalId),
-}
+    fn min_intrinsic_height(&self, tree: &'static WidgetTree, index: NodeIndex, width: f64) -> f64 {
+        let insets = self.insets(tree);
+        let width = (width - insets.x_value()).max(0.0);
+        let child = tree
+            .children(index)
+            .first()
+            .map(|&c| tree.query_min_intrinsic_height(c, width));
+        child.unwrap_or(0.0) + insets.y_value()
+    }
 
-trait QueryKey: Clone + std::hash::Hash + std::fmt::Debug + PartialEq + Eq {
-    type Output: Clone + std::fmt::Debug;
+    fn max_intrinsic_height(&self, tree: &'static WidgetTree, index: NodeIndex, width: f64) -> f64 {
+        let insets = self.insets(tree);
+        let width = (width - insets.x_value()).max(0.0);
+        let child = tree
+            .children(index)
+            .first()
+            .map(|&c| tree.query_max_intrinsic_height(c, width));
+        child.unwrap_or(0.0) + insets.y_value()
+    }
 
-    fn execute(&self, tree: &'static WidgetTree) -> Self::Output;
+    fn debug_padding(&self, tree: &'static WidgetTree, _index: NodeIndex) -> Insets {
+        self.insets(tree)
+    }
 }
 
-#[derive(Clone, Copy, Hash, Debug, PartialEq, Eq)]
-struct SignalId(usize);
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum PointerEventKind {
+    Down,
+    Move,
+    Up,
+}
 
-#[derive(Clone, Copy, Hash, Debug, PartialEq, Eq)]
-struct Signal<T> {
-    id: SignalId,
-    phantom: std::marker::PhantomData<T>,
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct PointerEvent {
+    kind: PointerEventKind,
+    /// Where the pointer is, in the coordinate space of the node receiving the event
+    position: Point,
 }
 
-#[derive(Clone, Copy, Hash, Debug, PartialEq, Eq)]
-struct NodePosition {
-    index: NodeIndex,
+/// How a node's children are composited into the scene: clipped to `clip`, faded by `alpha`, and
+/// blended with whatever was drawn underneath using `blend`. Maps onto a vello layer.
+#[derive(Clone, Debug)]
+struct Layer {
+    /// In the node's coordinate space. Children aren't clipped, or hit outside of their parent,
+    /// if this is `None`.
+    clip: Option<BezPath>,
+    alpha: f32,
+    blend: BlendMode,
 }
 
-impl QueryKey for NodePosition {
-    type Output = Point;
+impl Layer {
+    fn clip(shape: impl Shape) -> Self {
+        Layer {
+            clip: Some(shape.to_path(0.1)),
+            alpha: 1.0,
+            blend: Mix::Clip.into(),
+        }
+    }
 
-    fn execute(&self, tree: &'static WidgetTree) -> Self::Output {
-        if self.index == tree.root.borrow().unwrap() {
-            return Point::ORIGIN;
+    fn alpha(alpha: f32) -> Self {
+        Layer {
+            clip: None,
+            alpha,
+            blend: Mix::Normal.into(),
         }
+    }
 
-        let parent = tree
-            .tree
-            .borrow()
-            .neighbors_directed(self.index, petgraph::Direction::Incoming)
-            .next()
-            .unwrap();
+    fn blend(blend: impl Into<BlendMode>) -> Self {
+        Layer {
+            clip: None,
+            alpha: 1.0,
+            blend: blend.into(),
+        }
+    }
+
+    /// Whether `point` (in the node's coordinate space) can hit the node's children
+    fn contains(&self, point: Point) -> bool {
+        match &self.clip {
+            Some(clip) => clip.contains(point),
+            None => true,
+        }
+    }
+
+    /// Vello layers always have a clip, so unclipped layers clip to `bounds` instead, which
+    /// should cover everything drawn into the layer
+    fn push(&self, scene: &mut Scene, transform: Affine, bounds: Rect) {
+        match &self.clip {
+            Some(clip) => scene.push_layer(self.blend, self.alpha, transform, clip),
+            None => scene.push_layer(self.blend, self.alpha, transform, &bounds),
+        }
+    }
+}
+
+struct DrawerCtx<'a> {
+    /// Signals and queries read through the tree while drawing are tracked as paint
+    /// dependencies of the node, so the node is redrawn when they change
+    tree: &'static WidgetTree,
+    index: NodeIndex,
+    /// The node's bounds in its own coordinate space, i.e. with its origin at `(0, 0)`
+    rect: Rect,
+    /// Maps the node's coordinate space to the scene fragment being drawn, and should be passed
+    /// to every draw call
+    transform: Affine,
+    scene: &'a mut Scene,
+}
+
+trait Drawer {
+    fn draw(&self, ctx: DrawerCtx);
+
+    /// Draws on top of the node's children, e.g. a focus ring or border. Like `draw`, this is
+    /// outside of the node's layer, so it isn't clipped or faded along with the children.
+    fn draw_foreground(&self, _ctx: DrawerCtx) {}
+
+    /// Everywhere this drawer might draw, given the node's bounds. Drawers that spill outside
+    /// the node, like shadows, need to include that here or it won't be redrawn when it changes,
+    /// and may be cut off inside an ancestor's layer.
+    fn paint_bounds(&self, rect: Rect) -> Rect {
+        rect
+    }
+}
+
+/// What a node draws itself, in its own coordinate space. Children have fragments of their own,
+/// which are put together with this one when the frame is drawn.
+struct PaintFragment {
+    /// Drawn underneath the node's children
+    background: Scene,
+    /// Drawn on top of the node's children
+    foreground: Scene,
+    /// The layer the node's children are drawn into, if any
+    layer: Option<Layer>,
+    /// What the node's own drawing covers, or `None` if it doesn't draw anything
+    bounds: Option<Rect>,
+}
+
+/// Where a node's fragment ended up on screen, in physical pixels
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+struct PaintedBounds {
+    own: Option<Rect>,
+    subtree: Option<Rect>,
+}
+
+/// How a node was drawn in the last frame it was drawn in
+struct DrawnNode {
+    bounds: PaintedBounds,
+    /// The children drawn along with it
+    children: Vec<NodeIndex>,
+    /// Everything drawn for its subtree, if it's a repaint boundary
+    scene: Option<Scene>,
+}
+
+/// Damage being worked out while a frame is drawn
+struct FrameDamage {
+    /// Nodes whose own drawing may have changed since the frame before
+    painted: HashSet<NodeIndex>,
+    /// Nodes with anything in their subtree that may look different since the frame before.
+    /// Repaint boundaries that aren't in here are appended as they were drawn then.
+    dirty: HashSet<NodeIndex>,
+    /// Nodes drawn so far
+    drawn: HashSet<NodeIndex>,
+    /// Nodes that were drawn under a parent in the frame before but weren't this time. They're
+    /// gone from where they were, unless they end up drawn somewhere else.
+    removed: Vec<NodeIndex>,
+    damage: Damage,
+}
+
+/// The parts of the window that need redrawing, in physical pixels
+#[derive(Clone, Debug, Default, PartialEq)]
+struct Damage {
+    rects: Vec<Rect>,
+}
+
+impl Damage {
+    fn is_empty(&self) -> bool {
+        self.rects.is_empty()
+    }
+
+    fn add(&mut self, rect: Rect, clip: Rect) {
+        let rect = rect.intersect(clip);
+        if rect.width() > 0.0 && rect.height() > 0.0 {
+            self.rects.push(rect);
+        }
+    }
+}
+
+struct WidgetTreeWeight {
+    layouter: Rc<dyn Layouter>,
+    drawer: Option<Box<dyn Drawer>>,
+}
+
+#[derive(Clone, Copy, Hash, Debug, PartialEq, Eq)]
+enum QueryDependency {
+    NodePosition(NodeIndex),
+    NodeConstraints(NodeIndex),
+    NodeSize(NodeIndex),
+    NodeBaseline(NodeIndex),
+    NthChild(NthChild),
+    IntrinsicSize(IntrinsicSize),
+    LayoutMeasure(LayoutMeasure),
+    Signal(SignalId),
+    TextDirection,
+    ScaleFactor,
+    ViewportSize,
+    /// A node's list of children
+    Children(NodeIndex),
+    /// Which node, if any, a node is a child of
+    Parent(NodeIndex),
+    /// The scene fragment a node draws itself
+    NodePaint(NodeIndex),
+    /// Everything a node's subtree draws over
+    NodePaintBounds(NodeIndex),
+    /// Where a node is on screen
+    NodeTransform(NodeIndex),
+}
+
+trait QueryKey: Clone + std::hash::Hash + std::fmt::Debug + PartialEq + Eq {
+    type Output: Clone + std::fmt::Debug;
+
+    fn execute(&self, tree: &'static WidgetTree) -> Self::Output;
+}
+
+#[derive(Clone, Copy, Hash, Debug, PartialEq, Eq)]
+struct SignalId(usize);
+
+#[derive(Hash, Debug, PartialEq, Eq)]
+struct Signal<T> {
+    id: SignalId,
+    phantom: std::marker::PhantomData<T>,
+}
+
+// A signal is just a handle, so it's copyable whether or not its value is
+impl<T> Clone for Signal<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for Signal<T> {}
+
+/// A value that's either fixed up front or read from a signal
+#[derive(Clone, Copy, Debug)]
+enum MaybeSignal<T> {
+    Value(T),
+    Signal(Signal<T>),
+}
+
+impl<T: Clone + 'static> MaybeSignal<T> {
+    fn get(&self, tree: &'static WidgetTree) -> T {
+        match self {
+            MaybeSignal::Value(value) => value.clone(),
+            MaybeSignal::Signal(signal) => tree.get_signal(*signal),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Hash, Debug, PartialEq, Eq)]
+struct NodePosition {
+    index: NodeIndex,
+}
+
+impl QueryKey for NodePosition {
+    type Output = Point;
+
+    fn execute(&self, tree: &'static WidgetTree) -> Self::Output {
+        // Detached nodes (e.g. recycled list items) are laid out as if they were the root
+        let Some(parent) = tree.parent(self.index) else {
+            return Point::ORIGIN;
+        };
 
         // todo(chad): performance
         let child_n = tree
-            .tree
-            .borrow()
-            .neighbors_directed(parent, petgraph::Direction::Outgoing)
+            .children(parent)
+            .into_iter()
             .position(|n| n == self.index)
             .unwrap();
 
@@ -343,31 +1086,22 @@ impl QueryKey for NodeConstraints {
     type Output = Constraints;
 
     fn execute(&self, tree: &'static WidgetTree) -> Self::Output {
-        if self.index == tree.root.borrow().unwrap() {
-            return Constraints {
-                min: Size::ZERO,
-                max: tree.size.borrow().clone(),
-            };
-        }
-
-        let parent = tree
-            .tree
-            .borrow()
-            .neighbors_directed(self.index, petgraph::Direction::Incoming)
-            .next()
-            .unwrap();
+        // Detached nodes (e.g. recycled list items) are laid out as if they were the root
+        let Some(parent) = tree.parent(self.index) else {
+            return Constraints::loose(tree.logical_size());
+        };
 
         let parent_constraints = tree.query_node_constraints(parent);
 
         // todo(chad): performance
         let child_n = tree
-            .tree
-            .borrow()
-            .neighbors_directed(parent, petgraph::Direction::Outgoing)
+            .children(parent)
+            .into_iter()
             .position(|n| n == self.index)
             .unwrap();
 
-        tree.tree
+        let constraints = tree
+            .tree
             .borrow()
             .node_weight(parent)
             .unwrap()
@@ -379,7 +1113,10 @@ impl QueryKey for NodeConstraints {
                     child_n,
                     self_constraints: parent_constraints,
                 },
-            )
+            );
+
+        // A parent that hands out an empty range would leave the child with no valid size at all
+        constraints.normalize()
     }
 }
 
@@ -393,12 +1130,78 @@ impl QueryKey for NodeSize {
 
     fn execute(&self, tree: &'static WidgetTree) -> Self::Output {
         let constraints = tree.query_node_constraints(self.index);
-        tree.tree
+        let layouter = tree
+            .tree
+            .borrow()
+            .node_weight(self.index)
+            .unwrap()
+            .layouter
+            .clone();
+        let size = layouter.size_for_self(tree, self.index, LayouterSizeSelfCtx { constraints });
+
+        tree.record_overflow(self.index, constraints, size);
+
+        size
+    }
+}
+
+/// A node whose `size_for_self` didn't fit inside the constraints it was given, either by being
+/// bigger than the max or smaller than the min.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Overflow {
+    index: NodeIndex,
+    constraints: Constraints,
+    size: Size,
+    /// How far past the max constraints the node extends, on the trailing and bottom edges
+    excess: Size,
+    /// How far short of the min constraints the node falls
+    shortfall: Size,
+}
+
+impl Overflow {
+    /// Anything smaller than this is floating point noise rather than a real overflow
+    const TOLERANCE: f64 = 1e-6;
+
+    fn check(index: NodeIndex, constraints: Constraints, size: Size) -> Option<Self> {
+        let excess = Size::new(
+            (size.width.max(0.0) - constraints.max.width).max(0.0),
+            (size.height.max(0.0) - constraints.max.height).max(0.0),
+        );
+        let shortfall = Size::new(
+            (constraints.min.width - size.width).max(0.0),
+            (constraints.min.height - size.height).max(0.0),
+        );
+
+        [excess, shortfall]
+            .iter()
+            .any(|s| s.width > Self::TOLERANCE || s.height > Self::TOLERANCE)
+            .then_some(Overflow {
+                index,
+                constraints,
+                size,
+                excess,
+                shortfall,
+            })
+    }
+}
+
+#[derive(Clone, Copy, Hash, Debug, PartialEq, Eq)]
+struct NodeBaseline {
+    index: NodeIndex,
+}
+
+impl QueryKey for NodeBaseline {
+    type Output = Option<f64>;
+
+    fn execute(&self, tree: &'static WidgetTree) -> Self::Output {
+        let layouter = tree
+            .tree
             .borrow()
             .node_weight(self.index)
             .unwrap()
             .layouter
-            .size_for_self(tree, self.index, LayouterSizeSelfCtx { constraints })
+            .clone();
+        layouter.baseline(tree, self.index)
     }
 }
 
@@ -412,23 +1215,102 @@ impl QueryKey for NthChild {
     type Output = NodeIndex;
 
     fn execute(&self, tree: &'static WidgetTree) -> Self::Output {
-        let result = tree
+        tree.children(self.parent_index)[self.child_n]
+    }
+}
+
+#[derive(Clone, Copy, Hash, Debug, PartialEq, Eq)]
+enum IntrinsicDimension {
+    MinWidth,
+    MaxWidth,
+    MinHeight,
+    MaxHeight,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct IntrinsicSize {
+    index: NodeIndex,
+    dimension: IntrinsicDimension,
+    /// The node's extent on the other axis, e.g. the height for `MinWidth`
+    extent: f64,
+}
+
+impl std::hash::Hash for IntrinsicSize {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.index.hash(state);
+        self.dimension.hash(state);
+        canonical_bits(self.extent).hash(state);
+    }
+}
+
+impl PartialEq for IntrinsicSize {
+    fn eq(&self, other: &Self) -> bool {
+        self.index == other.index
+            && self.dimension == other.dimension
+            && canonical_bits(self.extent) == canonical_bits(other.extent)
+    }
+}
+
+impl std::cmp::Eq for IntrinsicSize {}
+
+impl QueryKey for IntrinsicSize {
+    type Output = f64;
+
+    fn execute(&self, tree: &'static WidgetTree) -> Self::Output {
+        let layouter = tree
             .tree
             .borrow()
-            .neighbors_directed(self.parent_index, petgraph::Direction::Outgoing)
-            .nth(self.child_n)
-            .unwrap();
+            .node_weight(self.index)
+            .unwrap()
+            .layouter
+            .clone();
 
-        result
+        match self.dimension {
+            IntrinsicDimension::MinWidth => {
+                layouter.min_intrinsic_width(tree, self.index, self.extent)
+            }
+            IntrinsicDimension::MaxWidth => {
+                layouter.max_intrinsic_width(tree, self.index, self.extent)
+            }
+            IntrinsicDimension::MinHeight => {
+                layouter.min_intrinsic_height(tree, self.index, self.extent)
+            }
+            IntrinsicDimension::MaxHeight => {
+                layouter.max_intrinsic_height(tree, self.index, self.extent)
+            }
+        }
     }
 }
 
+/// A measurement a layouter takes of its own children, cached separately from its size so that it
+/// only has to be retaken when the children it looked at change. `key` means whatever the
+/// layouter wants it to, e.g. a column number.
 #[derive(Clone, Copy, Hash, Debug, PartialEq, Eq)]
-struct RevisionId(usize);
+struct LayoutMeasure {
+    index: NodeIndex,
+    key: usize,
+}
+
+impl QueryKey for LayoutMeasure {
+    type Output = f64;
+
+    fn execute(&self, tree: &'static WidgetTree) -> Self::Output {
+        let layouter = tree
+            .tree
+            .borrow()
+            .node_weight(self.index)
+            .unwrap()
+            .layouter
+            .clone();
+        layouter.measure(tree, self.index, self.key)
+    }
+}
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug)]
 struct Revision {
+    /// When the output last came out different
     last_changed: usize,
+    /// When the output was last computed, or last checked against everything it read
     valid_through: usize,
 }
 
@@ -439,11 +1321,20 @@ struct CachedQueryOutput<T: Clone> {
 }
 
 struct WidgetTree {
+    /// The window's size in physical pixels. Layout happens in logical units, which are this
+    /// divided by the scale factor.
     size: RefCell<Size>,
+    scale_factor: RefCell<f64>,
+    text_direction: RefCell<TextDirection>,
     tree: RefCell<DiGraph<WidgetTreeWeight, ()>>,
     root: RefCell<Option<NodeIndex>>,
 
+    /// Bumped every time an input changes
     revision: RefCell<usize>,
+    /// When each input last changed. Inputs that never have aren't in here.
+    input_revisions: RefCell<HashMap<QueryDependency, usize>>,
+    /// Queries that read something that has changed since they were last computed or checked
+    stale: RefCell<HashSet<QueryDependency>>,
 
     signals: RefCell<HashMap<SignalId, Box<dyn Any>>>,
     query_stack: RefCell<Vec<QueryDependency>>,
@@ -454,19 +1345,47 @@ struct WidgetTree {
     node_position_query_cache: RefCell<HashMap<NodeIndex, CachedQueryOutput<Point>>>,
     node_size_query_cache: RefCell<HashMap<NodeIndex, CachedQueryOutput<Size>>>,
     node_constraints_query_cache: RefCell<HashMap<NodeIndex, CachedQueryOutput<Constraints>>>,
+    node_baseline_query_cache: RefCell<HashMap<NodeIndex, CachedQueryOutput<Option<f64>>>>,
     nth_child_query_cache: RefCell<HashMap<NthChild, CachedQueryOutput<NodeIndex>>>,
+    intrinsic_size_query_cache: RefCell<HashMap<IntrinsicSize, CachedQueryOutput<f64>>>,
+    layout_measure_query_cache: RefCell<HashMap<LayoutMeasure, CachedQueryOutput<f64>>>,
+    node_paint_query_cache: RefCell<HashMap<NodeIndex, CachedQueryOutput<Rc<PaintFragment>>>>,
+    node_paint_bounds_query_cache: RefCell<HashMap<NodeIndex, CachedQueryOutput<Option<Rect>>>>,
+    node_transform_query_cache: RefCell<HashMap<NodeIndex, CachedQueryOutput<Affine>>>,
 
     // Debug
     cache_ratio: RefCell<(u64, u64)>,
+    overflows: RefCell<HashMap<NodeIndex, Overflow>>,
+    debug_overlay: RefCell<bool>,
+    pointer_capture: RefCell<Option<NodeIndex>>,
+    /// Nodes whose size or position was recomputed since the last frame was drawn
+    recomputed: RefCell<HashSet<NodeIndex>>,
+    /// Nodes that read something while being drawn, or while their subtree was put together,
+    /// that has changed since
+    repaint: RefCell<HashSet<NodeIndex>>,
+    /// Nodes whose own drawing may have changed since they were last drawn, as opposed to only
+    /// their children's
+    paint_damaged: RefCell<HashSet<NodeIndex>>,
+    /// How each node was drawn in the last frame, including nodes in repaint boundaries that
+    /// were appended as they were
+    drawn: RefCell<HashMap<NodeIndex, DrawnNode>>,
+    /// The root as of the last frame
+    drawn_root: RefCell<Option<NodeIndex>>,
+    damage: RefCell<Damage>,
+    damage_all: RefCell<bool>,
 }
 
 impl WidgetTree {
     pub fn new() -> Self {
         Self {
             size: RefCell::new(Size::ZERO),
+            scale_factor: RefCell::new(1.0),
+            text_direction: RefCell::new(TextDirection::default()),
             tree: RefCell::new(DiGraph::new()),
             root: RefCell::new(None),
             revision: RefCell::new(0),
+            input_revisions: RefCell::new(HashMap::new()),
+            stale: RefCell::new(HashSet::new()),
             signals: RefCell::new(HashMap::new()),
             query_stack: RefCell::new(Vec::new()),
             dependency_tree: RefCell::new(DiGraph::new()),
@@ -474,11 +1393,30 @@ impl WidgetTree {
             node_position_query_cache: RefCell::new(HashMap::new()),
             node_size_query_cache: RefCell::new(HashMap::new()),
             node_constraints_query_cache: RefCell::new(HashMap::new()),
+            node_baseline_query_cache: RefCell::new(HashMap::new()),
             nth_child_query_cache: RefCell::new(HashMap::new()),
+            intrinsic_size_query_cache: RefCell::new(HashMap::new()),
+            layout_measure_query_cache: RefCell::new(HashMap::new()),
+            node_paint_query_cache: RefCell::new(HashMap::new()),
+            node_paint_bounds_query_cache: RefCell::new(HashMap::new()),
+            node_transform_query_cache: RefCell::new(HashMap::new()),
+            overflows: RefCell::new(HashMap::new()),
+            debug_overlay: RefCell::new(false),
+            pointer_capture: RefCell::new(None),
+            recomputed: RefCell::new(HashSet::new()),
+            repaint: RefCell::new(HashSet::new()),
+            paint_damaged: RefCell::new(HashSet::new()),
+            drawn: RefCell::new(HashMap::new()),
+            drawn_root: RefCell::new(None),
+            damage: RefCell::new(Damage::default()),
+            damage_all: RefCell::new(false),
             cache_ratio: RefCell::new((0, 1)),
         }
     }
 
+    /// Records that the query currently executing read `dep`. Queries call this even when their
+    /// output is already cached, since the query asking for it may be recomputing and needs to
+    /// record its dependencies again.
     fn track_dependency(&'static self, dep: QueryDependency) {
         let Some(q) = self.query_stack.borrow().last().cloned() else {
             return;
@@ -498,7 +1436,7 @@ impl WidgetTree {
             .clone();
         self.dependency_tree
             .borrow_mut()
-            .add_edge(q_node_index, dep_node_index, ());
+            .update_edge(q_node_index, dep_node_index, ());
     }
 
     pub fn create_signal<T: Clone + 'static>(&'static self, value: T) -> Signal<T> {
@@ -525,6 +1463,83 @@ impl WidgetTree {
         sig
     }
 
+    pub fn set_signal<T: Clone + 'static>(&'static self, signal: Signal<T>, value: T) {
+        *self
+            .signals
+            .borrow_mut()
+            .get_mut(&signal.id)
+            .unwrap()
+            .downcast_mut::<T>()
+            .unwrap() = value;
+
+        self.invalidate(QueryDependency::Signal(signal.id));
+    }
+
+    /// Replaces an input that queries read directly off the tree, invalidating whatever read it
+    fn set_input<T: PartialEq>(
+        &'static self,
+        input: &RefCell<T>,
+        value: T,
+        dependency: QueryDependency,
+    ) {
+        if *input.borrow() == value {
+            return;
+        }
+
+        *input.borrow_mut() = value;
+        self.invalidate(dependency);
+    }
+
+    /// The window's size in physical pixels
+    pub fn viewport_size(&'static self) -> Size {
+        self.track_dependency(QueryDependency::ViewportSize);
+        *self.size.borrow()
+    }
+
+    pub fn set_viewport_size(&'static self, size: Size) {
+        self.set_input(&self.size, size, QueryDependency::ViewportSize);
+    }
+
+    /// Physical pixels per logical unit
+    pub fn scale_factor(&'static self) -> f64 {
+        self.track_dependency(QueryDependency::ScaleFactor);
+        *self.scale_factor.borrow()
+    }
+
+    pub fn set_scale_factor(&'static self, scale_factor: f64) {
+        self.set_input(
+            &self.scale_factor,
+            scale_factor,
+            QueryDependency::ScaleFactor,
+        );
+    }
+
+    /// The window's size in the logical units that layout happens in
+    pub fn logical_size(&'static self) -> Size {
+        let scale_factor = self.scale_factor();
+        let size = self.viewport_size();
+        Size::new(size.width / scale_factor, size.height / scale_factor)
+    }
+
+    /// Maps logical units to physical pixels. Everything that is drawn or hit tested goes
+    /// through this first.
+    fn root_transform(&'static self) -> Affine {
+        Affine::scale(self.scale_factor())
+    }
+
+    pub fn text_direction(&'static self) -> TextDirection {
+        self.track_dependency(QueryDependency::TextDirection);
+        *self.text_direction.borrow()
+    }
+
+    pub fn set_text_direction(&'static self, direction: TextDirection) {
+        self.set_input(
+            &self.text_direction,
+            direction,
+            QueryDependency::TextDirection,
+        );
+    }
+
     pub fn add_node(
         &'static self,
         layouter: Box<dyn Layouter>,
@@ -533,7 +1548,10 @@ impl WidgetTree {
         let idx = self
             .tree
             .borrow_mut()
-            .add_node(WidgetTreeWeight { layouter, drawer });
+            .add_node(WidgetTreeWeight {
+                layouter: Rc::from(layouter),
+                drawer,
+            });
 
         if self.root.borrow().is_none() {
             *self.root.borrow_mut() = Some(idx)
@@ -554,6 +1572,9 @@ impl WidgetTree {
             .borrow_mut()
             .add_edge(parent_index, child_index, ());
 
+        self.invalidate(QueryDependency::Children(parent_index));
+        self.invalidate(QueryDependency::Parent(child_index));
+
         (parent_index, child_index)
     }
 
@@ -573,59 +1594,604 @@ impl WidgetTree {
         self.add_child(parent_index, child_index).1
     }
 
-    pub fn draw_index(&'static self, index: NodeIndex, scene: &mut Scene, offset_pos: Point) {
-        let position = {
-            let weight = self.tree.borrow();
-            let weight = weight.node_weight(index).unwrap();
-
-            let mut position: Point = self.query_node_position(index);
-            position.x += offset_pos.x;
-            position.y += offset_pos.y;
-
-            let size: Size = self.query_node_size(index);
+    /// Replaces all children of `parent`, so that `children[n]` becomes its `n`th child
+    pub fn set_children(&'static self, parent: NodeIndex, children: &[NodeIndex]) {
+        let old_children = self.children(parent);
+        {
+            let mut tree = self.tree.borrow_mut();
+            while let Some(edge) = tree.first_edge(parent, petgraph::Direction::Outgoing) {
+                tree.remove_edge(edge);
+            }
 
-            weight.drawer.as_ref().map(|d| {
-                d.draw(DrawerCtx {
-                    scene,
-                    rect: Rect::from_origin_size(position, size),
-                });
-            });
+            // Newer edges are visited first, so add them back to front
+            for &child in children.iter().rev() {
+                tree.add_edge(parent, child, ());
+            }
+        }
 
-            position
-        };
+        self.invalidate(QueryDependency::Children(parent));
+        for &child in old_children.iter().chain(children) {
+            self.invalidate(QueryDependency::Parent(child));
+        }
+    }
 
-        // todo(chad): performance
-        let neighbors = self
-            .tree
+    pub fn children(&'static self, index: NodeIndex) -> Vec<NodeIndex> {
+        self.track_dependency(QueryDependency::Children(index));
+        self.tree
             .borrow()
             .neighbors_directed(index, petgraph::Direction::Outgoing)
-            .collect::<Vec<_>>();
+            .collect()
+    }
 
-        for child in neighbors {
-            let offset_pos = Point::new(offset_pos.x + position.x, offset_pos.y + position.y);
-            self.draw_index(child, scene, offset_pos);
-        }
+    /// When the children of `index` last changed, for layouters that keep things worked out from
+    /// their children outside of the query caches. Reading it counts as reading the children.
+    pub fn children_revision(&'static self, index: NodeIndex) -> usize {
+        let children = QueryDependency::Children(index);
+        self.track_dependency(children);
+        self.input_revisions
+            .borrow()
+            .get(&children)
+            .copied()
+            .unwrap_or(0)
     }
 
-    pub fn draw(&'static self, scene: &mut Scene) {
-        let Some(root) = *self.root.borrow() else {
-            return;
-        };
-        self.draw_index(root, scene, Point::ORIGIN);
+    pub fn parent(&'static self, index: NodeIndex) -> Option<NodeIndex> {
+        self.track_dependency(QueryDependency::Parent(index));
+        self.tree
+            .borrow()
+            .neighbors_directed(index, petgraph::Direction::Incoming)
+            .next()
     }
 
-    fn current_revision(&'static self) -> Revision {
-        Revision {
-            last_changed: *self.revision.borrow(),
-            valid_through: *self.revision.borrow(),
-        }
+    pub fn parent_data<T: Clone + 'static>(&'static self, index: NodeIndex) -> Option<T> {
+        self.tree
+            .borrow()
+            .node_weight(index)
+            .unwrap()
+            .layouter
+            .parent_data()
+            .and_then(|d| d.downcast_ref::<T>())
+            .cloned()
     }
 
-    pub fn query_nth_child(&'static self, q: NthChild) -> NodeIndex {
-        self.cache_ratio.borrow_mut().1 += 1;
+    /// Maps the coordinate space of `index` into the coordinate space of its parent
+    fn local_transform(&'static self, index: NodeIndex) -> Affine {
+        let layouter = self.tree.borrow().node_weight(index).unwrap().layouter.clone();
+        let position = self.query_node_position(index);
+        Affine::translate(position.to_vec2()) * layouter.transform(self, index)
+    }
+
+    /// Maps the coordinate space of `index` into window coordinates. Its inverse maps window
+    /// coordinates (e.g. the pointer) into the node's own space.
+    fn node_transform(&'static self, index: NodeIndex) -> Affine {
+        let parent_transform = match self.parent(index) {
+            Some(parent) => self.query_node_transform(parent),
+            None => self.root_transform(),
+        };
+
+        parent_transform * self.local_transform(index)
+    }
+
+    /// The nodes under `point` (in physical window coordinates), from the root down to the topmost,
+    /// deepest node. Empty if the point is outside the root.
+    pub fn hit_test(&'static self, point: Point) -> Vec<NodeIndex> {
+        let mut path = Vec::new();
+        if let Some(root) = *self.root.borrow() {
+            self.hit_test_index(root, point, &mut path);
+        }
+        path
+    }
+
+    /// Sends a pointer event at `position` (in physical window coordinates) to the node that
+    /// captured the pointer, or else to the topmost node under it and then its ancestors until
+    /// one handles it. Returns whether anything did.
+    pub fn dispatch_pointer(&'static self, kind: PointerEventKind, position: Point) -> bool {
+        let captured = *self.pointer_capture.borrow();
+        let targets = match captured {
+            Some(index) => vec![index],
+            None => self.hit_test(position).into_iter().rev().collect(),
+        };
+
+        if kind == PointerEventKind::Up {
+            *self.pointer_capture.borrow_mut() = None;
+        }
+
+        for index in targets {
+            let layouter = self
+                .tree
+                .borrow()
+                .node_weight(index)
+                .unwrap()
+                .layouter
+                .clone();
+            let event = PointerEvent {
+                kind,
+                position: self.query_node_transform(index).inverse() * position,
+            };
+
+            if layouter.on_pointer(self, index, event) {
+                if kind == PointerEventKind::Down {
+                    *self.pointer_capture.borrow_mut() = Some(index);
+                }
+                return true;
+            }
+        }
+
+        false
+    }
+
+    /// Sends a scroll of `delta` at `position` (both in window coordinates) to the innermost node
+    /// under the pointer that handles it
+    pub fn dispatch_scroll(&'static self, position: Point, delta: Vec2) -> bool {
+        for index in self.hit_test(position).into_iter().rev() {
+            let layouter = self
+                .tree
+                .borrow()
+                .node_weight(index)
+                .unwrap()
+                .layouter
+                .clone();
+            let inverse = self.query_node_transform(index).inverse();
+            let delta = inverse * (position + delta) - inverse * position;
+
+            if layouter.on_scroll(self, index, delta) {
+                return true;
+            }
+        }
+
+        false
+    }
+
+    fn hit_test_index(
+        &'static self,
+        index: NodeIndex,
+        point: Point,
+        path: &mut Vec<NodeIndex>,
+    ) -> bool {
+        let local_point = self.query_node_transform(index).inverse() * point;
+        let rect = Rect::from_origin_size(Point::ORIGIN, self.query_node_size(index));
+        let inside = rect.contains(local_point);
+
+        let layouter = self
+            .tree
+            .borrow()
+            .node_weight(index)
+            .unwrap()
+            .layouter
+            .clone();
+        let layer = layouter.layer(self, index);
+        if layer.is_some_and(|layer| !layer.contains(local_point)) {
+            return false;
+        }
+
+        path.push(index);
+
+        // Later children paint on top, so they get the first chance to be hit
+        for child in self.children(index).into_iter().rev() {
+            if self.hit_test_index(child, point, path) {
+                return true;
+            }
+        }
+
+        if !inside {
+            path.pop();
+        }
+        inside
+    }
+
+    /// Draws what `index` draws itself into fresh scenes, in the node's own coordinate space.
+    /// Children aren't part of the fragment, so a node's drawing doesn't change with theirs.
+    fn paint_node(&'static self, index: NodeIndex) -> PaintFragment {
+        let mut background = Scene::new();
+        let mut foreground = Scene::new();
+        let transform = Affine::IDENTITY;
+
+        let size: Size = self.query_node_size(index);
+        let rect = Rect::from_origin_size(Point::ORIGIN, size);
+
+        let mut bounds: Option<Rect> = None;
+
+        let layer = {
+            let weight = self.tree.borrow();
+            let weight = weight.node_weight(index).unwrap();
+
+            weight.drawer.as_ref().map(|d| {
+                bounds = Some(d.paint_bounds(rect));
+                d.draw(DrawerCtx {
+                    tree: self,
+                    index,
+                    scene: &mut background,
+                    rect,
+                    transform,
+                });
+                d.draw_foreground(DrawerCtx {
+                    tree: self,
+                    index,
+                    scene: &mut foreground,
+                    rect,
+                    transform,
+                });
+            });
+
+            weight.layouter.clone()
+        }
+        .layer(self, index);
+
+        if cfg!(debug_assertions) {
+            if let Some(overflow) = self.overflow(index) {
+                draw_overflow_markers(&mut foreground, transform, &overflow, self.text_direction());
+                bounds = Some(bounds.map_or(rect, |bounds| bounds.union(rect)));
+            }
+        }
+
+        PaintFragment {
+            background,
+            foreground,
+            layer,
+            bounds,
+        }
+    }
+
+    /// Everything `index` and its subtree draw over, in the node's own coordinate space
+    fn paint_bounds(&'static self, index: NodeIndex) -> Option<Rect> {
+        let fragment = self.query_node_paint(index);
+
+        let mut children_bounds: Option<Rect> = None;
+
+        // Nothing in a fully transparent layer shows up
+        if !fragment
+            .layer
+            .as_ref()
+            .is_some_and(|layer| layer.alpha <= 0.0)
+        {
+            for child in self.children(index) {
+                if let Some(child_bounds) = self.query_node_paint_bounds(child) {
+                    let child_bounds = self
+                        .local_transform(child)
+                        .transform_rect_bbox(child_bounds);
+                    children_bounds = Some(
+                        children_bounds.map_or(child_bounds, |bounds| bounds.union(child_bounds)),
+                    );
+                }
+            }
+
+            let clip = fragment
+                .layer
+                .as_ref()
+                .and_then(|layer| layer.clip.as_ref());
+            if let Some(clip) = clip {
+                children_bounds = children_bounds.map(|b| b.intersect(clip.bounding_box()));
+            }
+        }
+
+        match (fragment.bounds, children_bounds) {
+            (Some(own), Some(children)) => Some(own.union(children)),
+            (own, children) => own.or(children),
+        }
+    }
+
+    /// Called every time a node is sized, so the overflow map always matches the size cache
+    fn record_overflow(&'static self, index: NodeIndex, constraints: Constraints, size: Size) {
+        match Overflow::check(index, constraints, size) {
+            Some(overflow) => {
+                self.overflows.borrow_mut().insert(index, overflow);
+            }
+            None => {
+                self.overflows.borrow_mut().remove(&index);
+            }
+        }
+    }
+
+    /// The overflow recorded the last time `index` was sized, if any
+    pub fn overflow(&'static self, index: NodeIndex) -> Option<Overflow> {
+        self.overflows.borrow().get(&index).copied()
+    }
+
+    /// Every node that overflowed the last time it was sized, in node order
+    pub fn overflows(&'static self) -> Vec<Overflow> {
+        let mut overflows = self
+            .overflows
+            .borrow()
+            .values()
+            .copied()
+            .collect::<Vec<_>>();
+        overflows.sort_by_key(|o| o.index);
+        overflows
+    }
+
+    /// Runs `Layouter::update` for every node reachable from the root, parents before children
+    pub fn update(&'static self) {
+        let Some(root) = *self.root.borrow() else {
+            return;
+        };
+
+        let mut stack = vec![root];
+        while let Some(index) = stack.pop() {
+            let layouter = self.tree.borrow().node_weight(index).unwrap().layouter.clone();
+            layouter.update(self, index);
+
+            stack.extend(self.children(index).into_iter().rev());
+        }
+    }
+
+    pub fn draw(&'static self, scene: &mut Scene) {
+        let Some(root) = *self.root.borrow() else {
+            return;
+        };
+        let viewport = Rect::from_origin_size(Point::ORIGIN, *self.size.borrow());
+        let painted = self.paint_damaged.take();
+
+        // Whatever may look different has to be drawn again, and so does everything above it,
+        // since that's where it's put together
+        let mut dirty = HashSet::new();
+        for &index in self.repaint.borrow().iter().chain(&painted) {
+            let mut node = Some(index);
+            while let Some(index) = node.filter(|&index| dirty.insert(index)) {
+                node = self.parent(index);
+            }
+        }
+
+        let mut frame = FrameDamage {
+            painted,
+            dirty,
+            drawn: HashSet::new(),
+            removed: self.drawn_root.replace(Some(root)).into_iter().collect(),
+            damage: Damage::default(),
+        };
+
+        // The debug overlay highlights whatever was laid out this frame, so it's all redrawn
+        if std::mem::take(&mut *self.damage_all.borrow_mut()) || *self.debug_overlay.borrow() {
+            frame.damage.add(viewport, viewport);
+        }
+
+        self.draw_index(root, scene, viewport, &mut frame);
+
+        // Whatever isn't drawn any more is gone from where it was, along with its subtree
+        while let Some(index) = frame.removed.pop() {
+            if frame.drawn.contains(&index) {
+                continue;
+            }
+            let Some(drawn) = self.drawn.borrow_mut().remove(&index) else {
+                continue;
+            };
+            if let Some(own) = drawn.bounds.own {
+                frame.damage.add(own, viewport);
+            }
+            frame.removed.extend(drawn.children);
+        }
+        *self.damage.borrow_mut() = frame.damage;
+
+        if *self.debug_overlay.borrow() {
+            self.draw_debug_overlay_index(root, scene);
+        }
+        self.recomputed.borrow_mut().clear();
+        self.repaint.borrow_mut().clear();
+    }
+
+    /// Appends the subtree of `index` to `scene`. Repaint boundaries with nothing in them that
+    /// changed are appended as they were drawn in the last frame, without visiting their subtree.
+    fn draw_index(
+        &'static self,
+        index: NodeIndex,
+        scene: &mut Scene,
+        clip: Rect,
+        frame: &mut FrameDamage,
+    ) {
+        let layouter = self
+            .tree
+            .borrow()
+            .node_weight(index)
+            .unwrap()
+            .layouter
+            .clone();
+        if !layouter.is_repaint_boundary() {
+            self.draw_node(index, scene, clip, frame);
+            return;
+        }
+
+        if !frame.dirty.contains(&index) {
+            if let Some(subtree) = self
+                .drawn
+                .borrow()
+                .get(&index)
+                .and_then(|drawn| drawn.scene.as_ref())
+            {
+                scene.append(subtree, None);
+                return;
+            }
+        }
+
+        // todo(chad): a boundary that only moved could be appended with a transform instead of
+        // being put together again
+        let mut subtree = Scene::new();
+        self.draw_node(index, &mut subtree, clip, frame);
+        scene.append(&subtree, None);
+        if let Some(drawn) = self.drawn.borrow_mut().get_mut(&index) {
+            drawn.scene = Some(subtree);
+        }
+    }
+
+    /// Appends the cached fragments of `index` and its subtree to `scene`, and works out the
+    /// damage along the way. Anything whose bounds on screen moved is damaged where it was and
+    /// where it is now, as is anything whose own drawing changed.
+    fn draw_node(
+        &'static self,
+        index: NodeIndex,
+        scene: &mut Scene,
+        clip: Rect,
+        frame: &mut FrameDamage,
+    ) {
+        let transform = self.query_node_transform(index);
+        let fragment = self.query_node_paint(index);
+        let subtree_bounds = self.query_node_paint_bounds(index);
+        let bounds = PaintedBounds {
+            own: fragment.bounds.map(|b| transform.transform_rect_bbox(b)),
+            subtree: subtree_bounds.map(|b| transform.transform_rect_bbox(b)),
+        };
+
+        // A node whose drawing changed may have changed how its whole subtree looks, e.g. by
+        // fading it. Otherwise children account for themselves, so only the node's own drawing
+        // moving around counts.
+        let (old_bounds, old_children) = match self.drawn.borrow_mut().remove(&index) {
+            Some(drawn) => (drawn.bounds, drawn.children),
+            None => (PaintedBounds::default(), Vec::new()),
+        };
+        let changed = if frame.painted.contains(&index) {
+            [old_bounds.subtree, bounds.subtree]
+        } else if old_bounds.own != bounds.own {
+            [old_bounds.own, bounds.own]
+        } else {
+            [None, None]
+        };
+        for rect in changed.into_iter().flatten() {
+            frame.damage.add(rect, clip);
+        }
+        frame.drawn.insert(index);
+
+        scene.append(&fragment.background, Some(transform));
+
+        // Children of a fully transparent layer aren't drawn, so they count as removed
+        let mut children = Vec::new();
+        if !fragment
+            .layer
+            .as_ref()
+            .is_some_and(|layer| layer.alpha <= 0.0)
+        {
+            let layer_clip = fragment
+                .layer
+                .as_ref()
+                .and_then(|layer| layer.clip.as_ref());
+            let child_clip = match layer_clip {
+                Some(layer_clip) => {
+                    clip.intersect(transform.transform_rect_bbox(layer_clip.bounding_box()))
+                }
+                None => clip,
+            };
+
+            if let Some(layer) = &fragment.layer {
+                layer.push(scene, transform, subtree_bounds.unwrap_or_default());
+            }
+            children = self.children(index);
+            for &child in &children {
+                self.draw_index(child, scene, child_clip, frame);
+            }
+            if fragment.layer.is_some() {
+                scene.pop_layer();
+            }
+        }
+
+        scene.append(&fragment.foreground, Some(transform));
+
+        frame.removed.extend(
+            old_children
+                .into_iter()
+                .filter(|child| !children.contains(child)),
+        );
+        self.drawn.borrow_mut().insert(
+            index,
+            DrawnNode {
+                bounds,
+                children,
+                scene: None,
+            },
+        );
+    }
+
+    /// What changed on screen in the frame last drawn, compared to the one before it. Nothing
+    /// needs rendering if this is empty.
+    pub fn damage(&'static self) -> Damage {
+        self.damage.borrow().clone()
+    }
+
+    /// Marks the whole window as damaged in the next frame, e.g. because the surface was lost
+    pub fn damage_all(&'static self) {
+        *self.damage_all.borrow_mut() = true;
+    }
 
-        if let Some(cached_output) = self.nth_child_query_cache.borrow().get(&q) {
-            if cached_output.revision.valid_through >= *self.revision.borrow() {
+    /// Whether anything drawn in the last frame has changed since, e.g. a signal a drawer read
+    pub fn needs_repaint(&'static self) -> bool {
+        !self.repaint.borrow().is_empty()
+    }
+
+    pub fn toggle_debug_overlay(&'static self) {
+        let enabled = !*self.debug_overlay.borrow();
+        *self.debug_overlay.borrow_mut() = enabled;
+        self.damage_all();
+    }
+
+    /// Outlines every node, shades padding and anything that was laid out again since the last
+    /// frame, and labels each node with its layouter, size and constraints
+    fn draw_debug_overlay_index(&'static self, index: NodeIndex, scene: &mut Scene) {
+        let transform = self.query_node_transform(index);
+
+        let layouter = self
+            .tree
+            .borrow()
+            .node_weight(index)
+            .unwrap()
+            .layouter
+            .clone();
+        let size = self.query_node_size(index);
+        let constraints = self.query_node_constraints(index);
+        let rect = Rect::from_origin_size(Point::ORIGIN, size);
+
+        if self.recomputed.borrow().contains(&index) {
+            scene.fill(
+                vello::peniko::Fill::NonZero,
+                transform,
+                Color::new([1.0, 0.0, 1.0, 0.15]),
+                None,
+                &rect,
+            );
+        }
+
+        let padding = layouter.debug_padding(self, index);
+        if padding != Insets::ZERO {
+            let mut region = rect.to_path(0.1);
+            region.extend((rect - padding).path_elements(0.1));
+            scene.fill(
+                vello::peniko::Fill::EvenOdd,
+                transform,
+                Color::new([1.0, 0.8, 0.0, 0.3]),
+                None,
+                &region,
+            );
+        }
+
+        scene.stroke(
+            &Stroke::new(1.0),
+            transform,
+            Color::new([0.0, 1.0, 1.0, 0.8]),
+            None,
+            &rect,
+        );
+
+        let format_size = |size: Size| format!("{:.0}x{:.0}", size.width, size.height);
+        draw_debug_label(
+            scene,
+            transform,
+            &[
+                &format!("{} {}", layouter.debug_name(), format_size(size)),
+                &format!(
+                    "{} - {}",
+                    format_size(constraints.min),
+                    format_size(constraints.max)
+                ),
+            ],
+        );
+
+        for child in self.children(index) {
+            self.draw_debug_overlay_index(child, scene);
+        }
+    }
+
+    pub fn query_nth_child(&'static self, q: NthChild) -> NodeIndex {
+        self.cache_ratio.borrow_mut().1 += 1;
+
+        self.track_dependency(QueryDependency::NthChild(q));
+
+        if self.verify(QueryDependency::NthChild(q)) {
+            if let Some(cached_output) = self.nth_child_query_cache.borrow().get(&q) {
                 self.cache_ratio.borrow_mut().0 += 1;
                 return cached_output.value;
             }
@@ -633,18 +2199,19 @@ impl WidgetTree {
 
         //println!("Recomputing {:?}", QueryDependency::NthChild(q));
 
-        self.track_dependency(QueryDependency::NthChild(q));
+        self.forget_dependencies(QueryDependency::NthChild(q));
         self.query_stack
             .borrow_mut()
             .push(QueryDependency::NthChild(q));
         let output = q.execute(self);
         self.query_stack.borrow_mut().pop().unwrap();
 
+        let revision = self.output_revision(self.nth_child_query_cache.borrow().get(&q), &output);
         self.nth_child_query_cache.borrow_mut().insert(
             q,
             CachedQueryOutput {
-                revision: self.current_revision(),
                 value: output,
+                revision,
             },
         );
         output
@@ -653,8 +2220,10 @@ impl WidgetTree {
     pub fn query_node_constraints(&'static self, q: NodeIndex) -> Constraints {
         self.cache_ratio.borrow_mut().1 += 1;
 
-        if let Some(cached_output) = self.node_constraints_query_cache.borrow().get(&q) {
-            if cached_output.revision.valid_through >= *self.revision.borrow() {
+        self.track_dependency(QueryDependency::NodeConstraints(q));
+
+        if self.verify(QueryDependency::NodeConstraints(q)) {
+            if let Some(cached_output) = self.node_constraints_query_cache.borrow().get(&q) {
                 self.cache_ratio.borrow_mut().0 += 1;
                 return cached_output.value;
             }
@@ -662,18 +2231,20 @@ impl WidgetTree {
 
         //println!("Recomputing {:?}", QueryDependency::NodeConstraints(q));
 
-        self.track_dependency(QueryDependency::NodeConstraints(q));
+        self.forget_dependencies(QueryDependency::NodeConstraints(q));
         self.query_stack
             .borrow_mut()
             .push(QueryDependency::NodeConstraints(q));
         let output = NodeConstraints { index: q }.execute(self);
         self.query_stack.borrow_mut().pop().unwrap();
 
+        let revision =
+            self.output_revision(self.node_constraints_query_cache.borrow().get(&q), &output);
         self.node_constraints_query_cache.borrow_mut().insert(
             q,
             CachedQueryOutput {
-                revision: self.current_revision(),
                 value: output,
+                revision,
             },
         );
         output
@@ -682,8 +2253,10 @@ impl WidgetTree {
     pub fn query_node_size(&'static self, q: NodeIndex) -> Size {
         self.cache_ratio.borrow_mut().1 += 1;
 
-        if let Some(cached_output) = self.node_size_query_cache.borrow().get(&q) {
-            if cached_output.revision.valid_through >= *self.revision.borrow() {
+        self.track_dependency(QueryDependency::NodeSize(q));
+
+        if self.verify(QueryDependency::NodeSize(q)) {
+            if let Some(cached_output) = self.node_size_query_cache.borrow().get(&q) {
                 self.cache_ratio.borrow_mut().0 += 1;
                 return cached_output.value;
             }
@@ -691,18 +2264,20 @@ impl WidgetTree {
 
         //println!("Recomputing {:?}", QueryDependency::NodeSize(q));
 
-        self.track_dependency(QueryDependency::NodeSize(q));
+        self.forget_dependencies(QueryDependency::NodeSize(q));
         self.query_stack
             .borrow_mut()
             .push(QueryDependency::NodeSize(q));
         let output = NodeSize { index: q }.execute(self);
         self.query_stack.borrow_mut().pop().unwrap();
+        self.recomputed.borrow_mut().insert(q);
 
+        let revision = self.output_revision(self.node_size_query_cache.borrow().get(&q), &output);
         self.node_size_query_cache.borrow_mut().insert(
             q,
             CachedQueryOutput {
-                revision: self.current_revision(),
                 value: output,
+                revision,
             },
         );
         output
@@ -711,8 +2286,10 @@ impl WidgetTree {
     pub fn query_node_position(&'static self, q: NodeIndex) -> Point {
         self.cache_ratio.borrow_mut().1 += 1;
 
-        if let Some(cached_output) = self.node_position_query_cache.borrow().get(&q) {
-            if cached_output.revision.valid_through >= *self.revision.borrow() {
+        self.track_dependency(QueryDependency::NodePosition(q));
+
+        if self.verify(QueryDependency::NodePosition(q)) {
+            if let Some(cached_output) = self.node_position_query_cache.borrow().get(&q) {
                 self.cache_ratio.borrow_mut().0 += 1;
                 return cached_output.value;
             }
@@ -720,171 +2297,519 @@ impl WidgetTree {
 
         //println!("Recomputing {:?}", QueryDependency::NodePosition(q));
 
-        self.track_dependency(QueryDependency::NodePosition(q));
+        self.forget_dependencies(QueryDependency::NodePosition(q));
         self.query_stack
             .borrow_mut()
             .push(QueryDependency::NodePosition(q));
         let output = NodePosition { index: q }.execute(self);
         self.query_stack.borrow_mut().pop().unwrap();
+        self.recomputed.borrow_mut().insert(q);
 
+        let revision =
+            self.output_revision(self.node_position_query_cache.borrow().get(&q), &output);
         self.node_position_query_cache.borrow_mut().insert(
             q,
             CachedQueryOutput {
-                revision: self.current_revision(),
                 value: output,
+                revision,
             },
         );
         output
     }
 
-    pub fn invalidate(&'static self, q: QueryDependency) {
-        let q_index = self.dependency_node_map.borrow().get(&q).unwrap().clone();
-
-        let q_parents = self
-            .dependency_tree
-            .borrow()
-            .neighbors_directed(q_index, petgraph::Direction::Incoming)
-            .collect::<Vec<_>>();
-
-        let mut to_invalidate = Vec::new();
+    /// The scene fragment for what a node draws itself. Neither the node's position nor its
+    /// children are part of its fragment, so moving or changing a child doesn't draw it again.
+    pub fn query_node_paint(&'static self, q: NodeIndex) -> Rc<PaintFragment> {
+        self.cache_ratio.borrow_mut().1 += 1;
 
-        for p in q_parents {
-            let p_dep = self
-                .dependency_tree
-                .borrow()
-                .node_weight(p)
-                .unwrap()
-                .clone();
+        self.track_dependency(QueryDependency::NodePaint(q));
 
-            match p_dep {
-                QueryDependency::NodePosition(node_index) => {
-                    let old_value = self
-                        .node_position_query_cache
-                        .borrow_mut()
-                        .get(&node_index)
-                        .cloned()
-                        .unwrap();
-
-                    let new_value = self.query_node_position(node_index);
-
-                    if new_value == old_value.value {
-                        self.node_position_query_cache.borrow_mut().insert(
-                            node_index,
-                            CachedQueryOutput {
-                                value: new_value,
-                                revision: Revision {
-                                    last_changed: old_value.revision.last_changed,
-                                    valid_through: *self.revision.borrow(),
-                                },
-                            },
-                        );
-                    } else {
-                        to_invalidate.push(p_dep);
-                    }
-                }
-                QueryDependency::NodeConstraints(node_index) => {
-                    let old_value = self
-                        .node_constraints_query_cache
-                        .borrow_mut()
-                        .get(&node_index)
-                        .cloned()
-                        .unwrap();
-
-                    let new_value = self.query_node_constraints(node_index);
-
-                    if new_value == old_value.value {
-                        self.node_constraints_query_cache.borrow_mut().insert(
-                            node_index,
-                            CachedQueryOutput {
-                                value: new_value,
-                                revision: Revision {
-                                    last_changed: old_value.revision.last_changed,
-                                    valid_through: *self.revision.borrow(),
-                                },
-                            },
-                        );
-                    } else {
-                        to_invalidate.push(p_dep);
-                    }
-                }
-                QueryDependency::NodeSize(node_index) => {
-                    let old_value = self
-                        .node_size_query_cache
-                        .borrow_mut()
-                        .get(&node_index)
-                        .cloned()
-                        .unwrap();
-
-                    let new_value = self.query_node_size(node_index);
-
-                    if new_value == old_value.value {
-                        self.node_size_query_cache.borrow_mut().insert(
-                            node_index,
-                            CachedQueryOutput {
-                                value: new_value,
-                                revision: Revision {
-                                    last_changed: old_value.revision.last_changed,
-                                    valid_through: *self.revision.borrow(),
-                                },
-                            },
-                        );
-                    } else {
-                        to_invalidate.push(p_dep);
-                    }
-                }
-                QueryDependency::NthChild(nth_child) => {
-                    let old_value = self
-                        .nth_child_query_cache
-                        .borrow_mut()
-                        .get(&nth_child)
-                        .cloned()
-                        .unwrap();
-
-                    let new_value = self.query_nth_child(nth_child);
-
-                    if new_value == old_value.value {
-                        self.nth_child_query_cache.borrow_mut().insert(
-                            nth_child,
-                            CachedQueryOutput {
-                                value: new_value,
-                                revision: Revision {
-                                    last_changed: old_value.revision.last_changed,
-                                    valid_through: *self.revision.borrow(),
-                                },
-                            },
-                        );
-                    } else {
-                        to_invalidate.push(p_dep);
-                    }
-                }
-                QueryDependency::Signal(_) => {
-                    panic!("A signal should never depend on another thing");
-                }
-            };
+        if self.verify(QueryDependency::NodePaint(q)) {
+            if let Some(cached_output) = self.node_paint_query_cache.borrow().get(&q) {
+                self.cache_ratio.borrow_mut().0 += 1;
+                return cached_output.value.clone();
+            }
         }
 
-        for dep in to_invalidate {
-            self.invalidate(dep);
-        }
+        self.forget_dependencies(QueryDependency::NodePaint(q));
+        self.query_stack
+            .borrow_mut()
+            .push(QueryDependency::NodePaint(q));
+        let output = Rc::new(self.paint_node(q));
+        self.query_stack.borrow_mut().pop().unwrap();
+
+        // Fragments can't be compared, so a node drawn again always counts as changed
+        let revision = self.current_revision();
+        self.node_paint_query_cache.borrow_mut().insert(
+            q,
+            CachedQueryOutput {
+                value: output.clone(),
+                revision,
+            },
+        );
+        output
     }
 
-    pub fn reset(&'static self) {
-        println!("******* Resetting!!");
+    pub fn query_node_paint_bounds(&'static self, q: NodeIndex) -> Option<Rect> {
+        self.cache_ratio.borrow_mut().1 += 1;
 
-        println!("==========================");
-        println!("{:?}", self.dependency_tree.borrow());
-        println!("==========================");
+        self.track_dependency(QueryDependency::NodePaintBounds(q));
 
-        *self.revision.borrow_mut() = 0;
-        *self.cache_ratio.borrow_mut() = (0, 1);
+        if self.verify(QueryDependency::NodePaintBounds(q)) {
+            if let Some(cached_output) = self.node_paint_bounds_query_cache.borrow().get(&q) {
+                self.cache_ratio.borrow_mut().0 += 1;
+                return cached_output.value;
+            }
+        }
 
-        self.dependency_node_map.borrow_mut().clear();
-        self.dependency_tree.borrow_mut().clear();
+        self.forget_dependencies(QueryDependency::NodePaintBounds(q));
+        self.query_stack
+            .borrow_mut()
+            .push(QueryDependency::NodePaintBounds(q));
+        let output = self.paint_bounds(q);
+        self.query_stack.borrow_mut().pop().unwrap();
 
-        self.node_position_query_cache.borrow_mut().clear();
+        let revision =
+            self.output_revision(self.node_paint_bounds_query_cache.borrow().get(&q), &output);
+        self.node_paint_bounds_query_cache.borrow_mut().insert(
+            q,
+            CachedQueryOutput {
+                value: output,
+                revision,
+            },
+        );
+        output
+    }
+
+    pub fn query_node_transform(&'static self, q: NodeIndex) -> Affine {
+        self.cache_ratio.borrow_mut().1 += 1;
+
+        self.track_dependency(QueryDependency::NodeTransform(q));
+
+        if self.verify(QueryDependency::NodeTransform(q)) {
+            if let Some(cached_output) = self.node_transform_query_cache.borrow().get(&q) {
+                self.cache_ratio.borrow_mut().0 += 1;
+                return cached_output.value;
+            }
+        }
+
+        self.forget_dependencies(QueryDependency::NodeTransform(q));
+        self.query_stack
+            .borrow_mut()
+            .push(QueryDependency::NodeTransform(q));
+        let output = self.node_transform(q);
+        self.query_stack.borrow_mut().pop().unwrap();
+
+        let revision =
+            self.output_revision(self.node_transform_query_cache.borrow().get(&q), &output);
+        self.node_transform_query_cache.borrow_mut().insert(
+            q,
+            CachedQueryOutput {
+                value: output,
+                revision,
+            },
+        );
+        output
+    }
+
+    pub fn query_node_baseline(&'static self, q: NodeIndex) -> Option<f64> {
+        self.cache_ratio.borrow_mut().1 += 1;
+
+        self.track_dependency(QueryDependency::NodeBaseline(q));
+
+        if self.verify(QueryDependency::NodeBaseline(q)) {
+            if let Some(cached_output) = self.node_baseline_query_cache.borrow().get(&q) {
+                self.cache_ratio.borrow_mut().0 += 1;
+                return cached_output.value;
+            }
+        }
+
+        self.forget_dependencies(QueryDependency::NodeBaseline(q));
+        self.query_stack
+            .borrow_mut()
+            .push(QueryDependency::NodeBaseline(q));
+        let output = NodeBaseline { index: q }.execute(self);
+        self.query_stack.borrow_mut().pop().unwrap();
+
+        let revision =
+            self.output_revision(self.node_baseline_query_cache.borrow().get(&q), &output);
+        self.node_baseline_query_cache.borrow_mut().insert(
+            q,
+            CachedQueryOutput {
+                value: output,
+                revision,
+            },
+        );
+        output
+    }
+
+    pub fn query_intrinsic_size(&'static self, q: IntrinsicSize) -> f64 {
+        self.cache_ratio.borrow_mut().1 += 1;
+
+        self.track_dependency(QueryDependency::IntrinsicSize(q));
+
+        if self.verify(QueryDependency::IntrinsicSize(q)) {
+            if let Some(cached_output) = self.intrinsic_size_query_cache.borrow().get(&q) {
+                self.cache_ratio.borrow_mut().0 += 1;
+                return cached_output.value;
+            }
+        }
+
+        self.forget_dependencies(QueryDependency::IntrinsicSize(q));
+        self.query_stack
+            .borrow_mut()
+            .push(QueryDependency::IntrinsicSize(q));
+        let output = q.execute(self);
+        self.query_stack.borrow_mut().pop().unwrap();
+
+        let revision =
+            self.output_revision(self.intrinsic_size_query_cache.borrow().get(&q), &output);
+        self.intrinsic_size_query_cache.borrow_mut().insert(
+            q,
+            CachedQueryOutput {
+                value: output,
+                revision,
+            },
+        );
+        output
+    }
+
+    pub fn query_layout_measure(&'static self, q: LayoutMeasure) -> f64 {
+        self.cache_ratio.borrow_mut().1 += 1;
+
+        self.track_dependency(QueryDependency::LayoutMeasure(q));
+
+        if self.verify(QueryDependency::LayoutMeasure(q)) {
+            if let Some(cached_output) = self.layout_measure_query_cache.borrow().get(&q) {
+                self.cache_ratio.borrow_mut().0 += 1;
+                return cached_output.value;
+            }
+        }
+
+        self.forget_dependencies(QueryDependency::LayoutMeasure(q));
+        self.query_stack
+            .borrow_mut()
+            .push(QueryDependency::LayoutMeasure(q));
+        let output = q.execute(self);
+        self.query_stack.borrow_mut().pop().unwrap();
+
+        let revision =
+            self.output_revision(self.layout_measure_query_cache.borrow().get(&q), &output);
+        self.layout_measure_query_cache.borrow_mut().insert(
+            q,
+            CachedQueryOutput {
+                value: output,
+                revision,
+            },
+        );
+        output
+    }
+
+    pub fn query_min_intrinsic_width(&'static self, index: NodeIndex, height: f64) -> f64 {
+        self.query_intrinsic_size(IntrinsicSize {
+            index,
+            dimension: IntrinsicDimension::MinWidth,
+            extent: height,
+        })
+    }
+
+    pub fn query_max_intrinsic_width(&'static self, index: NodeIndex, height: f64) -> f64 {
+        self.query_intrinsic_size(IntrinsicSize {
+            index,
+            dimension: IntrinsicDimension::MaxWidth,
+            extent: height,
+        })
+    }
+
+    pub fn query_min_intrinsic_height(&'static self, index: NodeIndex, width: f64) -> f64 {
+        self.query_intrinsic_size(IntrinsicSize {
+            index,
+            dimension: IntrinsicDimension::MinHeight,
+            extent: width,
+        })
+    }
+
+    pub fn query_max_intrinsic_height(&'static self, index: NodeIndex, width: f64) -> f64 {
+        self.query_intrinsic_size(IntrinsicSize {
+            index,
+            dimension: IntrinsicDimension::MaxHeight,
+            extent: width,
+        })
+    }
+
+    fn current_revision(&'static self) -> Revision {
+        Revision {
+            last_changed: *self.revision.borrow(),
+            valid_through: *self.revision.borrow(),
+        }
+    }
+
+    /// The revision for a query's new output, which only counts as changed if it came out
+    /// different from `old`
+    fn output_revision<T: Clone + PartialEq>(
+        &'static self,
+        old: Option<&CachedQueryOutput<T>>,
+        output: &T,
+    ) -> Revision {
+        match old {
+            Some(old) if old.value == *output => Revision {
+                last_changed: old.revision.last_changed,
+                valid_through: *self.revision.borrow(),
+            },
+            _ => self.current_revision(),
+        }
+    }
+
+    /// Runs `f` on the revision of `q`'s cached output, if it has one
+    fn with_revision<T>(
+        &'static self,
+        q: QueryDependency,
+        f: impl FnOnce(&mut Revision) -> T,
+    ) -> Option<T> {
+        fn cached<K: std::hash::Hash + Eq, V: Clone, T>(
+            cache: &RefCell<HashMap<K, CachedQueryOutput<V>>>,
+            key: &K,
+            f: impl FnOnce(&mut Revision) -> T,
+        ) -> Option<T> {
+            cache
+                .borrow_mut()
+                .get_mut(key)
+                .map(|cached_output| f(&mut cached_output.revision))
+        }
+
+        match q {
+            QueryDependency::NodePosition(node_index) => {
+                cached(&self.node_position_query_cache, &node_index, f)
+            }
+            QueryDependency::NodeConstraints(node_index) => {
+                cached(&self.node_constraints_query_cache, &node_index, f)
+            }
+            QueryDependency::NodeSize(node_index) => {
+                cached(&self.node_size_query_cache, &node_index, f)
+            }
+            QueryDependency::NodeBaseline(node_index) => {
+                cached(&self.node_baseline_query_cache, &node_index, f)
+            }
+            QueryDependency::NthChild(nth_child) => {
+                cached(&self.nth_child_query_cache, &nth_child, f)
+            }
+            QueryDependency::IntrinsicSize(intrinsic_size) => {
+                cached(&self.intrinsic_size_query_cache, &intrinsic_size, f)
+            }
+            QueryDependency::LayoutMeasure(layout_measure) => {
+                cached(&self.layout_measure_query_cache, &layout_measure, f)
+            }
+            QueryDependency::NodePaint(node_index) => {
+                cached(&self.node_paint_query_cache, &node_index, f)
+            }
+            QueryDependency::NodePaintBounds(node_index) => {
+                cached(&self.node_paint_bounds_query_cache, &node_index, f)
+            }
+            QueryDependency::NodeTransform(node_index) => {
+                cached(&self.node_transform_query_cache, &node_index, f)
+            }
+            // Inputs aren't cached
+            QueryDependency::Signal(_)
+            | QueryDependency::TextDirection
+            | QueryDependency::ScaleFactor
+            | QueryDependency::ViewportSize
+            | QueryDependency::Children(_)
+            | QueryDependency::Parent(_) => None,
+        }
+    }
+
+    /// When `dependency` last changed, bringing it up to date first if it's a query
+    fn last_changed(&'static self, dependency: QueryDependency) -> usize {
+        match dependency {
+            QueryDependency::NodePosition(node_index) => {
+                self.query_node_position(node_index);
+            }
+            QueryDependency::NodeConstraints(node_index) => {
+                self.query_node_constraints(node_index);
+            }
+            QueryDependency::NodeSize(node_index) => {
+                self.query_node_size(node_index);
+            }
+            QueryDependency::NodeBaseline(node_index) => {
+                self.query_node_baseline(node_index);
+            }
+            QueryDependency::NthChild(nth_child) => {
+                self.query_nth_child(nth_child);
+            }
+            QueryDependency::IntrinsicSize(intrinsic_size) => {
+                self.query_intrinsic_size(intrinsic_size);
+            }
+            QueryDependency::LayoutMeasure(layout_measure) => {
+                self.query_layout_measure(layout_measure);
+            }
+            QueryDependency::NodePaint(node_index) => {
+                self.query_node_paint(node_index);
+            }
+            QueryDependency::NodePaintBounds(node_index) => {
+                self.query_node_paint_bounds(node_index);
+            }
+            QueryDependency::NodeTransform(node_index) => {
+                self.query_node_transform(node_index);
+            }
+            QueryDependency::Signal(_)
+            | QueryDependency::TextDirection
+            | QueryDependency::ScaleFactor
+            | QueryDependency::ViewportSize
+            | QueryDependency::Children(_)
+            | QueryDependency::Parent(_) => {
+                return self
+                    .input_revisions
+                    .borrow()
+                    .get(&dependency)
+                    .copied()
+                    .unwrap_or(0);
+            }
+        }
+
+        self.with_revision(dependency, |revision| revision.last_changed)
+            .unwrap()
+    }
+
+    /// Whether the cached output of `q`, if there is one, is still up to date. A stale query is
+    /// checked by bringing everything it read up to date, in the order it read it, and is only
+    /// recomputed if some of that came out different. This is the early cutoff: a change that
+    /// doesn't alter a query's output stops there instead of reaching everything downstream.
+    fn verify(&'static self, q: QueryDependency) -> bool {
+        if !self.stale.borrow().contains(&q) {
+            return true;
+        }
+        let Some(revision) = self.with_revision(q, |revision| *revision) else {
+            return false;
+        };
+
+        // Reads are checked in order, so a query is never asked for that `q` wouldn't ask for
+        // again, e.g. the fifth child of a node that now has three
+        let dependencies = match self.dependency_node_map.borrow().get(&q) {
+            Some(&index) => {
+                let dependency_tree = self.dependency_tree.borrow();
+                let mut dependencies = dependency_tree
+                    .neighbors_directed(index, petgraph::Direction::Outgoing)
+                    .map(|dependency| dependency_tree[dependency])
+                    .collect::<Vec<_>>();
+                dependencies.reverse();
+                dependencies
+            }
+            None => Vec::new(),
+        };
+
+        // Whatever is asking for `q` didn't read these itself, so don't record them against it
+        let query_stack = self.query_stack.take();
+        let unchanged = dependencies
+            .into_iter()
+            .all(|dependency| self.last_changed(dependency) <= revision.valid_through);
+        *self.query_stack.borrow_mut() = query_stack;
+
+        if unchanged {
+            self.stale.borrow_mut().remove(&q);
+            let current = *self.revision.borrow();
+            self.with_revision(q, |revision| revision.valid_through = current);
+        }
+        unchanged
+    }
+
+    /// Forgets what `q` read last time, since it's about to be recomputed and read it all again
+    fn forget_dependencies(&'static self, q: QueryDependency) {
+        self.stale.borrow_mut().remove(&q);
+
+        let Some(&index) = self.dependency_node_map.borrow().get(&q) else {
+            return;
+        };
+        let mut dependency_tree = self.dependency_tree.borrow_mut();
+        while let Some(edge) = dependency_tree.first_edge(index, petgraph::Direction::Outgoing) {
+            dependency_tree.remove_edge(edge);
+        }
+    }
+
+    /// Records that the input `q` changed, and marks every query that read it, directly or
+    /// through other queries, as stale. Stale queries keep their output until they're next asked
+    /// for, when they're checked and only recomputed if something they read came out different.
+    pub fn invalidate(&'static self, q: QueryDependency) {
+        *self.revision.borrow_mut() += 1;
+        let revision = *self.revision.borrow();
+        self.input_revisions.borrow_mut().insert(q, revision);
+
+        // Nothing has read this yet, so nothing can be out of date
+        let Some(q_index) = self.dependency_node_map.borrow().get(&q).cloned() else {
+            return;
+        };
+
+        let mut visited = HashSet::new();
+        let mut stack = vec![q_index];
+        while let Some(index) = stack.pop() {
+            if !visited.insert(index) {
+                continue;
+            }
+
+            let dependency_tree = self.dependency_tree.borrow();
+            let dependency = *dependency_tree.node_weight(index).unwrap();
+            for dependent in
+                dependency_tree.neighbors_directed(index, petgraph::Direction::Incoming)
+            {
+                // Sizes and positions only change how a node looks by moving or resizing it, which
+                // shows up as a change in bounds. Anything else a node read while drawing may
+                // change how the node itself looks.
+                if let QueryDependency::NodePaint(node) = dependency_tree[dependent] {
+                    if !matches!(
+                        dependency,
+                        QueryDependency::NodeSize(_) | QueryDependency::NodePosition(_)
+                    ) {
+                        self.paint_damaged.borrow_mut().insert(node);
+                    }
+                }
+                stack.push(dependent);
+            }
+
+            if index != q_index {
+                self.mark_stale(dependency);
+            }
+        }
+    }
+
+    fn mark_stale(&'static self, dependency: QueryDependency) {
+        self.stale.borrow_mut().insert(dependency);
+
+        // Paint bounds read the children and where they are, and transforms are where nodes are
+        // drawn, so the frame needs putting together again whenever they're stale, even if
+        // nothing is drawn again
+        if let QueryDependency::NodePaint(node_index)
+        | QueryDependency::NodePaintBounds(node_index)
+        | QueryDependency::NodeTransform(node_index) = dependency
+        {
+            self.repaint.borrow_mut().insert(node_index);
+        }
+    }
+
+    pub fn reset(&'static self) {
+        println!("******* Resetting!!");
+
+        println!("==========================");
+        println!("{:?}", self.dependency_tree.borrow());
+        println!("==========================");
+
+        *self.revision.borrow_mut() = 0;
+        *self.cache_ratio.borrow_mut() = (0, 1);
+
+        self.input_revisions.borrow_mut().clear();
+        self.stale.borrow_mut().clear();
+        self.dependency_node_map.borrow_mut().clear();
+        self.dependency_tree.borrow_mut().clear();
+
+        self.node_position_query_cache.borrow_mut().clear();
         self.node_size_query_cache.borrow_mut().clear();
         self.node_constraints_query_cache.borrow_mut().clear();
+        self.node_baseline_query_cache.borrow_mut().clear();
+        self.overflows.borrow_mut().clear();
         self.nth_child_query_cache.borrow_mut().clear();
+        self.intrinsic_size_query_cache.borrow_mut().clear();
+        self.layout_measure_query_cache.borrow_mut().clear();
+        self.node_paint_query_cache.borrow_mut().clear();
+        self.node_paint_bounds_query_cache.borrow_mut().clear();
+        self.node_transform_query_cache.borrow_mut().clear();
     }
 }
 
@@ -924,7 +2849,9 @@ impl ApplicationHandler for SimpleVelloApp<'_> {
         // Create a vello Surface
         let size = window.inner_size();
 
-        *self.widget_tree.size.borrow_mut() = Size::new(size.width as f64, size.height as f64);
+        self.widget_tree
+            .set_viewport_size(Size::new(size.width as f64, size.height as f64));
+        self.widget_tree.set_scale_factor(window.scale_factor());
 
         let surface_future = self.context.create_surface(
             window.clone(),
@@ -940,8 +2867,16 @@ impl ApplicationHandler for SimpleVelloApp<'_> {
         self.renderers[surface.dev_id]
             .get_or_insert_with(|| create_vello_renderer(&self.context, &surface));
 
+        // Nothing has been rendered to the new surface yet
+        let frame = FrameTextures::new(&self.context.devices[surface.dev_id].device, &surface);
+        self.widget_tree.damage_all();
+
         // Save the Window and Surface to a state variable
-        self.state = RenderState::Active(ActiveRenderState { window, surface });
+        self.state = RenderState::Active(ActiveRenderState {
+            window,
+            surface,
+            frame,
+        });
     }
 
     fn suspended(&mut self, _event_loop: &ActiveEventLoop) {
@@ -950,6 +2885,16 @@ impl ApplicationHandler for SimpleVelloApp<'_> {
         }
     }
 
+    fn about_to_wait(&mut self, _event_loop: &ActiveEventLoop) {
+        // Signals that only affect drawing don't go through any event that would ask for a
+        // redraw, so check for them once all events have been handled
+        if let RenderState::Active(state) = &self.state {
+            if self.widget_tree.needs_repaint() {
+                state.window.request_redraw();
+            }
+        }
+    }
+
     fn window_event(
         &mut self,
         event_loop: &ActiveEventLoop,
@@ -967,15 +2912,115 @@ impl ApplicationHandler for SimpleVelloApp<'_> {
             WindowEvent::Resized(size) => {
                 self.context
                     .resize_surface(&mut render_state.surface, size.width, size.height);
-                *self.widget_tree.size.borrow_mut() =
-                    Size::new(size.width as f64, size.height as f64);
+                render_state.frame = FrameTextures::new(
+                    &self.context.devices[render_state.surface.dev_id].device,
+                    &render_state.surface,
+                );
+                self.widget_tree
+                    .set_viewport_size(Size::new(size.width as f64, size.height as f64));
+                // Resizing the surface throws away what was on it
+                self.widget_tree.damage_all();
+                render_state.window.request_redraw();
+            }
+
+            // A `Resized` with the new physical size follows this if the window changes size too
+            WindowEvent::ScaleFactorChanged { scale_factor, .. } => {
+                self.widget_tree.set_scale_factor(scale_factor);
+                render_state.window.request_redraw();
+            }
+
+            WindowEvent::Occluded(false) => {
+                self.widget_tree.damage_all();
+                render_state.window.request_redraw();
+            }
+
+            WindowEvent::ModifiersChanged(modifiers) => {
+                self.modifiers = modifiers.state();
+            }
+
+            // Ctrl+Shift+D toggles the layout debug overlay
+            WindowEvent::KeyboardInput { event, .. }
+                if event.state == ElementState::Pressed
+                    && !event.repeat
+                    && event.physical_key == PhysicalKey::Code(KeyCode::KeyD)
+                    && self.modifiers.control_key()
+                    && self.modifiers.shift_key() =>
+            {
+                self.widget_tree.toggle_debug_overlay();
+                render_state.window.request_redraw();
+            }
+
+            // Ctrl+Shift+R flips the text direction, to check how the layout mirrors
+            WindowEvent::KeyboardInput { event, .. }
+                if event.state == ElementState::Pressed
+                    && !event.repeat
+                    && event.physical_key == PhysicalKey::Code(KeyCode::KeyR)
+                    && self.modifiers.control_key()
+                    && self.modifiers.shift_key() =>
+            {
+                let direction = match self.widget_tree.text_direction() {
+                    TextDirection::Ltr => TextDirection::Rtl,
+                    TextDirection::Rtl => TextDirection::Ltr,
+                };
+                self.widget_tree.set_text_direction(direction);
+                render_state.window.request_redraw();
+            }
+
+            WindowEvent::CursorMoved { position, .. } => {
+                self.cursor_position = Point::new(position.x, position.y);
+                if self
+                    .widget_tree
+                    .dispatch_pointer(PointerEventKind::Move, self.cursor_position)
+                {
+                    render_state.window.request_redraw();
+                }
+            }
+
+            WindowEvent::MouseWheel { delta, .. } => {
+                // Wheels scroll the content up when turned down, so the deltas are flipped
+                let delta = match delta {
+                    MouseScrollDelta::LineDelta(x, y) => {
+                        -Vec2::new(x as f64, y as f64)
+                            * SCROLL_LINE_HEIGHT
+                            * render_state.window.scale_factor()
+                    }
+                    MouseScrollDelta::PixelDelta(position) => -Vec2::new(position.x, position.y),
+                };
+                if self
+                    .widget_tree
+                    .dispatch_scroll(self.cursor_position, delta)
+                {
+                    render_state.window.request_redraw();
+                }
             }
 
+            WindowEvent::MouseInput {
+                state: ElementState::Released,
+                button: MouseButton::Left,
+                ..
+            } => {
+                if self
+                    .widget_tree
+                    .dispatch_pointer(PointerEventKind::Up, self.cursor_position)
+                {
+                    render_state.window.request_redraw();
+                }
+            }
+
+            // Only the left button is sent to the tree, so every `Down` has a matching `Up`
             WindowEvent::MouseInput {
                 state: ElementState::Pressed,
+                button,
                 ..
             } => {
-                *self.widget_tree.revision.borrow_mut() += 1;
+                if button == MouseButton::Left
+                    && self
+                        .widget_tree
+                        .dispatch_pointer(PointerEventKind::Down, self.cursor_position)
+                {
+                    render_state.window.request_redraw();
+                    return;
+                }
 
                 self.widget_tree
                     .signals
@@ -996,11 +3041,6 @@ impl ApplicationHandler for SimpleVelloApp<'_> {
             }
 
             WindowEvent::RedrawRequested => {
-                *self.widget_tree.revision.borrow_mut() += 1;
-                //if *self.widget_tree.revision.borrow() > 100 {
-                //    self.widget_tree.reset();
-                //}
-
                  println!(
                      "Cache ratio: {:?}",
                      self.widget_tree.cache_ratio.borrow().0 as f64
@@ -1011,26 +3051,50 @@ impl ApplicationHandler for SimpleVelloApp<'_> {
 
                 let surface = &render_state.surface;
 
+                self.widget_tree.update();
                 self.widget_tree.draw(&mut self.scene);
 
+                // Nothing on screen has changed, so what was presented last time is still right
+                let damage = self.widget_tree.damage();
+                if damage.is_empty() {
+                    return;
+                }
+
                 let width = surface.config.width;
                 let height = surface.config.height;
 
-                let device_handle = &self.context.devices[surface.dev_id];
+                // Only whole pixels can be copied, so damage is rounded out to them
+                let viewport = Rect::new(0.0, 0.0, width as f64, height as f64);
+                let damaged: Vec<Rect> = damage
+                    .rects
+                    .iter()
+                    .map(|rect| rect.expand().intersect(viewport))
+                    .filter(|rect| rect.area() > 0.0)
+                    .collect();
+                let mut clip = BezPath::new();
+                for rect in &damaged {
+                    clip.extend(rect.path_elements(0.0));
+                }
 
-                let surface_texture = surface
-                    .surface
-                    .get_current_texture()
-                    .expect("failed to get surface texture");
+                // Vello still goes over the whole surface, but everything outside of the clip
+                // is culled before it's drawn
+                self.damaged_scene.reset();
+                self.damaged_scene
+                    .push_layer(Mix::Clip, 1.0, Affine::IDENTITY, &clip);
+                self.damaged_scene.append(&self.scene, None);
+                self.damaged_scene.pop_layer();
+
+                let device_handle = &self.context.devices[surface.dev_id];
+                let frame = &render_state.frame;
 
                 self.renderers[surface.dev_id]
                     .as_mut()
                     .unwrap()
-                    .render_to_surface(
+                    .render_to_texture(
                         &device_handle.device,
                         &device_handle.queue,
-                        &self.scene,
-                        &surface_texture,
+                        &self.damaged_scene,
+                        &frame.rendered_view,
                         &vello::RenderParams {
                             base_color: palette::css::BLACK, // Background color
                             width,
@@ -1038,7 +3102,54 @@ impl ApplicationHandler for SimpleVelloApp<'_> {
                             antialiasing_method: AaConfig::Msaa16,
                         },
                     )
-                    .expect("failed to render to surface");
+                    .expect("failed to render to texture");
+
+                // Everything outside of the damage is background in what was just rendered, so
+                // only the damaged rects are copied over the last frame
+                let mut encoder = device_handle
+                    .device
+                    .create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+                for rect in damaged {
+                    let origin = wgpu::Origin3d {
+                        x: rect.x0 as u32,
+                        y: rect.y0 as u32,
+                        z: 0,
+                    };
+                    encoder.copy_texture_to_texture(
+                        wgpu::ImageCopyTexture {
+                            texture: &frame.rendered,
+                            mip_level: 0,
+                            origin,
+                            aspect: wgpu::TextureAspect::All,
+                        },
+                        wgpu::ImageCopyTexture {
+                            texture: &frame.presented,
+                            mip_level: 0,
+                            origin,
+                            aspect: wgpu::TextureAspect::All,
+                        },
+                        wgpu::Extent3d {
+                            width: rect.width() as u32,
+                            height: rect.height() as u32,
+                            depth_or_array_layers: 1,
+                        },
+                    );
+                }
+
+                let surface_texture = surface
+                    .surface
+                    .get_current_texture()
+                    .expect("failed to get surface texture");
+                let surface_view = surface_texture
+                    .texture
+                    .create_view(&wgpu::TextureViewDescriptor::default());
+                frame.blitter.copy(
+                    &device_handle.device,
+                    &mut encoder,
+                    &frame.presented_view,
+                    &surface_view,
+                );
+                device_handle.queue.submit([encoder.finish()]);
 
                 // Queue the texture to be presented on the surface
                 surface_texture.present();
@@ -1050,42 +3161,631 @@ impl ApplicationHandler for SimpleVelloApp<'_> {
     }
 }
 
-struct SimpleQuadDrawer {
-    color: [f32; 3],
-}
-
-impl Drawer for SimpleQuadDrawer {
-    fn draw(&self, ctx: DrawerCtx) {
-        let [r, g, b] = self.color;
-        let [dr, dg, db] = [(r + 1.0) / 2.0, (g + 1.0) / 2.0, (b + 1.0) / 2.0];
-
-        // Draw an outlined rectangle
-        let stroke = Stroke::new(6.0);
-        let rect = RoundedRect::new(ctx.rect.x0, ctx.rect.y0, ctx.rect.x1, ctx.rect.y1, 20.0);
-        let rect_stroke_color = Color::new([dr, dg, db, 1.]);
-        let rect_fill_color = Color::new([r, g, b, 1.]);
-        ctx.scene.fill(
-            vello::peniko::Fill::NonZero,
-            Affine::IDENTITY,
-            rect_fill_color,
-            None,
-            &rect,
-        );
-        ctx.scene
-            .stroke(&stroke, Affine::IDENTITY, rect_stroke_color, None, &rect);
+/// A 3x5 bitmap glyph for the debug overlay, one row per entry with the leftmost pixel in the
+/// highest bit. Letters are all uppercase, and anything unknown is drawn as a question mark.
+fn debug_glyph(c: char) -> [u8; 5] {
+    match c.to_ascii_uppercase() {
+        'A' => [2, 5, 7, 5, 5],
+        'B' => [6, 5, 6, 5, 6],
+        'C' => [3, 4, 4, 4, 3],
+        'D' => [6, 5, 5, 5, 6],
+        'E' => [7, 4, 6, 4, 7],
+        'F' => [7, 4, 6, 4, 4],
+        'G' => [3, 4, 5, 5, 3],
+        'H' => [5, 5, 7, 5, 5],
+        'I' => [7, 2, 2, 2, 7],
+        'J' => [1, 1, 1, 5, 2],
+        'K' => [5, 5, 6, 5, 5],
+        'L' => [4, 4, 4, 4, 7],
+        'M' => [5, 7, 7, 5, 5],
+        'N' => [6, 5, 5, 5, 5],
+        'O' => [2, 5, 5, 5, 2],
+        'P' => [6, 5, 6, 4, 4],
+        'Q' => [2, 5, 5, 6, 3],
+        'R' => [6, 5, 6, 5, 5],
+        'S' => [3, 4, 2, 1, 6],
+        'T' => [7, 2, 2, 2, 2],
+        'U' => [5, 5, 5, 5, 7],
+        'V' => [5, 5, 5, 5, 2],
+        'W' => [5, 5, 7, 7, 5],
+        'X' => [5, 5, 2, 5, 5],
+        'Y' => [5, 5, 2, 2, 2],
+        'Z' => [7, 1, 2, 4, 7],
+        '0' => [7, 5, 5, 5, 7],
+        '1' => [2, 6, 2, 2, 7],
+        '2' => [6, 1, 2, 4, 7],
+        '3' => [6, 1, 2, 1, 6],
+        '4' => [5, 5, 7, 1, 1],
+        '5' => [7, 4, 6, 1, 6],
+        '6' => [3, 4, 6, 5, 2],
+        '7' => [7, 1, 2, 2, 2],
+        '8' => [2, 5, 2, 5, 2],
+        '9' => [2, 5, 3, 1, 6],
+        '.' => [0, 0, 0, 0, 2],
+        ',' => [0, 0, 0, 2, 4],
+        ':' => [0, 2, 0, 2, 0],
+        '-' => [0, 0, 7, 0, 0],
+        '=' => [0, 7, 0, 7, 0],
+        '<' => [1, 2, 4, 2, 1],
+        '>' => [4, 2, 1, 2, 4],
+        '(' => [1, 2, 2, 2, 1],
+        ')' => [4, 2, 2, 2, 4],
+        '[' => [6, 4, 4, 4, 6],
+        ']' => [3, 1, 1, 1, 3],
+        ' ' => [0, 0, 0, 0, 0],
+        _ => [7, 1, 2, 0, 2],
     }
 }
 
-struct CenteredLayouter { }
-
-impl Layouter for CenteredLayouter {
-    fn constraints_for_child(
-        &self,
+/// Draws `lines` of text at the top-left of a node, on a dark background so it stays readable
+/// over whatever the node drew
+fn draw_debug_label(scene: &mut Scene, transform: Affine, lines: &[&str]) {
+    const PIXEL: f64 = 2.0;
+    const ADVANCE: f64 = 4.0 * PIXEL;
+    const LINE_HEIGHT: f64 = 6.0 * PIXEL;
+
+    let longest = lines.iter().map(|l| l.chars().count()).max().unwrap_or(0);
+    let background = Rect::new(
+        0.0,
+        0.0,
+        longest as f64 * ADVANCE + PIXEL,
+        lines.len() as f64 * LINE_HEIGHT + PIXEL,
+    );
+    scene.fill(
+        vello::peniko::Fill::NonZero,
+        transform,
+        Color::new([0.0, 0.0, 0.0, 0.6]),
+        None,
+        &background,
+    );
+
+    let mut pixels = BezPath::new();
+    for (line_n, line) in lines.iter().enumerate() {
+        for (char_n, c) in line.chars().enumerate() {
+            let origin = Point::new(
+                PIXEL + char_n as f64 * ADVANCE,
+                PIXEL + line_n as f64 * LINE_HEIGHT,
+            );
+
+            for (row, bits) in debug_glyph(c).into_iter().enumerate() {
+                for column in 0..3 {
+                    if bits & (4 >> column) != 0 {
+                        let pixel = Rect::from_origin_size(
+                            origin + (column as f64 * PIXEL, row as f64 * PIXEL),
+                            (PIXEL, PIXEL),
+                        );
+                        pixels.extend(pixel.path_elements(0.1));
+                    }
+                }
+            }
+        }
+    }
+    scene.fill(
+        vello::peniko::Fill::NonZero,
+        transform,
+        palette::css::WHITE,
+        None,
+        &pixels,
+    );
+}
+
+/// Yellow and black hazard stripes along whichever edges of the node ended up past or short of
+/// its constraints
+const OVERFLOW_MARKER_THICKNESS: f64 = 8.0;
+
+/// Where the markers for `overflow` go: along the trailing edge for width and the bottom edge
+/// for height, since that's where an overflowing node spills over
+fn overflow_marker_edges(overflow: &Overflow, direction: TextDirection) -> Vec<Rect> {
+    let size = overflow.size;
+    let mut edges = Vec::new();
+    if overflow.excess.width > 0.0 || overflow.shortfall.width > 0.0 {
+        edges.push(match direction {
+            TextDirection::Ltr => Rect::new(
+                size.width - OVERFLOW_MARKER_THICKNESS,
+                0.0,
+                size.width,
+                size.height,
+            ),
+            TextDirection::Rtl => Rect::new(0.0, 0.0, OVERFLOW_MARKER_THICKNESS, size.height),
+        });
+    }
+    if overflow.excess.height > 0.0 || overflow.shortfall.height > 0.0 {
+        edges.push(Rect::new(
+            0.0,
+            size.height - OVERFLOW_MARKER_THICKNESS,
+            size.width,
+            size.height,
+        ));
+    }
+    edges
+}
+
+fn draw_overflow_markers(
+    scene: &mut Scene,
+    transform: Affine,
+    overflow: &Overflow,
+    direction: TextDirection,
+) {
+    let stripe = Stroke::new(OVERFLOW_MARKER_THICKNESS / 2.0);
+    for edge in overflow_marker_edges(overflow, direction) {
+        scene.fill(
+            vello::peniko::Fill::NonZero,
+            transform,
+            palette::css::YELLOW,
+            None,
+            &edge,
+        );
+
+        scene.push_layer(Mix::Clip, 1.0, transform, &edge);
+        let mut x = edge.x0 - edge.height();
+        while x < edge.x1 {
+            let line = Line::new((x, edge.y1), (x + edge.height(), edge.y0));
+            scene.stroke(&stripe, transform, palette::css::BLACK, None, &line);
+            x += OVERFLOW_MARKER_THICKNESS * 1.5;
+        }
+        scene.pop_layer();
+    }
+}
+
+/// What fills a `BoxDecoration`. Gradient geometry is given relative to the box, so it stretches
+/// with it: points are `Alignment`s within the box, and a radial gradient's radius is a fraction
+/// of the box's shortest side.
+#[derive(Clone, Debug, PartialEq)]
+enum BoxFill {
+    Solid(Color),
+    Linear {
+        begin: Alignment,
+        end: Alignment,
+        stops: Vec<(f32, Color)>,
+    },
+    Radial {
+        center: Alignment,
+        radius: f64,
+        stops: Vec<(f32, Color)>,
+    },
+    /// Angles are in radians, clockwise from the positive x axis
+    Sweep {
+        center: Alignment,
+        start_angle: f32,
+        end_angle: f32,
+        stops: Vec<(f32, Color)>,
+    },
+}
+
+impl From<Color> for BoxFill {
+    fn from(color: Color) -> Self {
+        BoxFill::Solid(color)
+    }
+}
+
+impl BoxFill {
+    fn brush(&self, rect: Rect, direction: TextDirection) -> Brush {
+        let point = |alignment: &Alignment| {
+            rect.origin()
+                + alignment
+                    .resolve(direction)
+                    .position(rect.size(), Size::ZERO)
+                    .to_vec2()
+        };
+
+        match self {
+            BoxFill::Solid(color) => Brush::Solid(*color),
+            BoxFill::Linear { begin, end, stops } => Gradient::new_linear(point(begin), point(end))
+                .with_stops(stops.as_slice())
+                .into(),
+            BoxFill::Radial {
+                center,
+                radius,
+                stops,
+            } => {
+                let radius = radius * rect.width().min(rect.height());
+                Gradient::new_radial(point(center), radius as f32)
+                    .with_stops(stops.as_slice())
+                    .into()
+            }
+            BoxFill::Sweep {
+                center,
+                start_angle,
+                end_angle,
+                stops,
+            } => Gradient::new_sweep(point(center), *start_angle, *end_angle)
+                .with_stops(stops.as_slice())
+                .into(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct BorderSide {
+    width: f64,
+    color: Color,
+}
+
+impl BorderSide {
+    const NONE: BorderSide = BorderSide {
+        width: 0.0,
+        color: palette::css::TRANSPARENT,
+    };
+
+    fn new(width: f64, color: Color) -> Self {
+        BorderSide { width, color }
+    }
+}
+
+/// Borders are drawn inside the box. Where two sides with different colors meet, the corner is
+/// split diagonally between them.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Border {
+    top: BorderSide,
+    right: BorderSide,
+    bottom: BorderSide,
+    left: BorderSide,
+}
+
+impl Border {
+    fn all(width: f64, color: Color) -> Self {
+        let side = BorderSide::new(width, color);
+        Border {
+            top: side,
+            right: side,
+            bottom: side,
+            left: side,
+        }
+    }
+
+    fn is_uniform(&self) -> bool {
+        [self.right, self.bottom, self.left]
+            .iter()
+            .all(|side| *side == self.top)
+    }
+
+    fn insets(&self) -> Insets {
+        Insets::new(
+            self.left.width,
+            self.top.width,
+            self.right.width,
+            self.bottom.width,
+        )
+    }
+}
+
+/// A blurred copy of the box drawn behind it, moved by `offset` and grown by `spread`
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct BoxShadow {
+    color: Color,
+    offset: Vec2,
+    blur_radius: f64,
+    spread: f64,
+}
+
+impl BoxShadow {
+    fn std_dev(&self) -> f64 {
+        self.blur_radius / 2.0
+    }
+
+    /// The box the shadow is cast by, before blurring
+    fn rect(&self, rect: Rect) -> Rect {
+        rect.inflate(self.spread, self.spread) + self.offset
+    }
+
+    /// Where the shadow is visible. A gaussian blur fades out to nothing within three standard
+    /// deviations.
+    fn paint_bounds(&self, rect: Rect) -> Rect {
+        let blur = 3.0 * self.std_dev();
+        self.rect(rect).inflate(blur, blur)
+    }
+}
+
+/// Draws a box with rounded corners, shadows, a fill and a border, in that order. Later shadows
+/// are drawn on top of earlier ones. The fill can come from a signal, e.g. to change color on
+/// hover, in which case changing it redraws the box without laying anything out again.
+#[derive(Clone, Debug)]
+struct BoxDecoration {
+    fill: Option<MaybeSignal<BoxFill>>,
+    border: Option<Border>,
+    radii: RoundedRectRadii,
+    shadows: Vec<BoxShadow>,
+}
+
+impl BoxDecoration {
+    fn new() -> Self {
+        BoxDecoration {
+            fill: None,
+            border: None,
+            radii: RoundedRectRadii::default(),
+            shadows: Vec::new(),
+        }
+    }
+
+    fn fill(self, fill: impl Into<BoxFill>) -> Self {
+        BoxDecoration {
+            fill: Some(MaybeSignal::Value(fill.into())),
+            ..self
+        }
+    }
+
+    fn fill_signal(self, fill: Signal<BoxFill>) -> Self {
+        BoxDecoration {
+            fill: Some(MaybeSignal::Signal(fill)),
+            ..self
+        }
+    }
+
+    fn border(self, border: Border) -> Self {
+        BoxDecoration {
+            border: Some(border),
+            ..self
+        }
+    }
+
+    fn radius(self, radii: impl Into<RoundedRectRadii>) -> Self {
+        BoxDecoration {
+            radii: radii.into(),
+            ..self
+        }
+    }
+
+    fn shadow(mut self, shadow: BoxShadow) -> Self {
+        self.shadows.push(shadow);
+        self
+    }
+
+    fn draw_shadow(&self, ctx: &mut DrawerCtx, shadow: &BoxShadow) {
+        let rect = shadow.rect(ctx.rect);
+
+        // Vello can only blur boxes with the same radius on every corner
+        let radius = [
+            self.radii.top_left,
+            self.radii.top_right,
+            self.radii.bottom_right,
+            self.radii.bottom_left,
+        ]
+        .into_iter()
+        .fold(0.0, f64::max);
+
+        ctx.scene.draw_blurred_rounded_rect(
+            ctx.transform,
+            rect,
+            shadow.color,
+            (radius + shadow.spread).max(0.0),
+            shadow.std_dev(),
+        );
+    }
+
+    fn draw_border(&self, ctx: &mut DrawerCtx, border: &Border) {
+        let outer = ctx.rect;
+        let inner = outer - border.insets();
+        let inner = Rect::new(
+            inner.x0,
+            inner.y0,
+            inner.x1.max(inner.x0),
+            inner.y1.max(inner.y0),
+        );
+
+        // The inside edge of each corner curves less, by however far the border sticks into it
+        let inner_radii = RoundedRectRadii::new(
+            (self.radii.top_left - border.left.width.max(border.top.width)).max(0.0),
+            (self.radii.top_right - border.right.width.max(border.top.width)).max(0.0),
+            (self.radii.bottom_right - border.right.width.max(border.bottom.width)).max(0.0),
+            (self.radii.bottom_left - border.left.width.max(border.bottom.width)).max(0.0),
+        );
+
+        let mut ring = RoundedRect::from_rect(outer, self.radii).to_path(0.1);
+        ring.extend(RoundedRect::from_rect(inner, inner_radii).path_elements(0.1));
+
+        if border.is_uniform() {
+            ctx.scene.fill(
+                vello::peniko::Fill::EvenOdd,
+                ctx.transform,
+                border.top.color,
+                None,
+                &ring,
+            );
+            return;
+        }
+
+        // Each side gets the part of the ring between the lines joining its outer and inner
+        // corners
+        let corners = |rect: Rect| {
+            [
+                Point::new(rect.x0, rect.y0),
+                Point::new(rect.x1, rect.y0),
+                Point::new(rect.x1, rect.y1),
+                Point::new(rect.x0, rect.y1),
+            ]
+        };
+        let (outer, inner) = (corners(outer), corners(inner));
+        let sides = [border.top, border.right, border.bottom, border.left];
+
+        for (n, side) in sides.into_iter().enumerate() {
+            if side.width <= 0.0 {
+                continue;
+            }
+
+            let next = (n + 1) % 4;
+            let mut wedge = BezPath::new();
+            wedge.move_to(outer[n]);
+            wedge.line_to(outer[next]);
+            wedge.line_to(inner[next]);
+            wedge.line_to(inner[n]);
+            wedge.close_path();
+
+            ctx.scene.push_layer(Mix::Clip, 1.0, ctx.transform, &wedge);
+            ctx.scene.fill(
+                vello::peniko::Fill::EvenOdd,
+                ctx.transform,
+                side.color,
+                None,
+                &ring,
+            );
+            ctx.scene.pop_layer();
+        }
+    }
+}
+
+impl Drawer for BoxDecoration {
+    fn draw(&self, mut ctx: DrawerCtx) {
+        for shadow in &self.shadows {
+            self.draw_shadow(&mut ctx, shadow);
+        }
+
+        if let Some(fill) = &self.fill {
+            let brush = fill
+                .get(ctx.tree)
+                .brush(ctx.rect, ctx.tree.text_direction());
+            ctx.scene.fill(
+                vello::peniko::Fill::NonZero,
+                ctx.transform,
+                &brush,
+                None,
+                &RoundedRect::from_rect(ctx.rect, self.radii),
+            );
+        }
+
+        if let Some(border) = &self.border {
+            self.draw_border(&mut ctx, border);
+        }
+    }
+
+    fn paint_bounds(&self, rect: Rect) -> Rect {
+        self.shadows.iter().fold(rect, |bounds, shadow| {
+            bounds.union(shadow.paint_bounds(rect))
+        })
+    }
+}
+
+/// A point within a box, where `(-1.0, -1.0)` is the top-left corner, `(0.0, 0.0)` is the
+/// center and `(1.0, 1.0)` is the bottom-right corner.
+///
+/// A `directional` alignment measures `x` from the start edge instead, so `-1.0` is the left
+/// edge for left-to-right text and the right edge for right-to-left text. Use `resolve` to get
+/// the physical alignment.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Alignment {
+    x: f64,
+    y: f64,
+    directional: bool,
+}
+
+impl Alignment {
+    const TOP_LEFT: Alignment = Alignment {
+        x: -1.0,
+        y: -1.0,
+        directional: false,
+    };
+    const TOP_CENTER: Alignment = Alignment {
+        x: 0.0,
+        y: -1.0,
+        directional: false,
+    };
+    const TOP_RIGHT: Alignment = Alignment {
+        x: 1.0,
+        y: -1.0,
+        directional: false,
+    };
+    const CENTER_LEFT: Alignment = Alignment {
+        x: -1.0,
+        y: 0.0,
+        directional: false,
+    };
+    const CENTER: Alignment = Alignment {
+        x: 0.0,
+        y: 0.0,
+        directional: false,
+    };
+    const CENTER_RIGHT: Alignment = Alignment {
+        x: 1.0,
+        y: 0.0,
+        directional: false,
+    };
+    const BOTTOM_LEFT: Alignment = Alignment {
+        x: -1.0,
+        y: 1.0,
+        directional: false,
+    };
+    const BOTTOM_CENTER: Alignment = Alignment {
+        x: 0.0,
+        y: 1.0,
+        directional: false,
+    };
+    const BOTTOM_RIGHT: Alignment = Alignment {
+        x: 1.0,
+        y: 1.0,
+        directional: false,
+    };
+
+    const TOP_START: Alignment = Alignment::directional(-1.0, -1.0);
+    const TOP_END: Alignment = Alignment::directional(1.0, -1.0);
+    const CENTER_START: Alignment = Alignment::directional(-1.0, 0.0);
+    const CENTER_END: Alignment = Alignment::directional(1.0, 0.0);
+    const BOTTOM_START: Alignment = Alignment::directional(-1.0, 1.0);
+    const BOTTOM_END: Alignment = Alignment::directional(1.0, 1.0);
+
+    fn new(x: f64, y: f64) -> Self {
+        Alignment {
+            x,
+            y,
+            directional: false,
+        }
+    }
+
+    const fn directional(x: f64, y: f64) -> Self {
+        Alignment {
+            x,
+            y,
+            directional: true,
+        }
+    }
+
+    /// The physical alignment for text flowing in `direction`
+    fn resolve(&self, direction: TextDirection) -> Alignment {
+        let x = match (self.directional, direction) {
+            (true, TextDirection::Rtl) => -self.x,
+            _ => self.x,
+        };
+        Alignment::new(x, self.y)
+    }
+
+    /// Where a box of size `child` should go inside a box of size `parent`, once resolved
+    fn position(&self, parent: Size, child: Size) -> Point {
+        Point::new(
+            (parent.width - child.width) * (self.x + 1.0) / 2.0,
+            (parent.height - child.height) * (self.y + 1.0) / 2.0,
+        )
+    }
+}
+
+/// Positions its child according to `alignment`. Without a factor, takes up all the space its
+/// constraints allow on that axis (or shrinks to the child when unbounded). With a factor, sizes
+/// itself to a multiple of the child on that axis.
+struct Align {
+    alignment: Alignment,
+    width_factor: Option<f64>,
+    height_factor: Option<f64>,
+}
+
+impl Align {
+    fn new(alignment: Alignment) -> Self {
+        Align {
+            alignment,
+            width_factor: None,
+            height_factor: None,
+        }
+    }
+
+    fn center() -> Self {
+        Align::new(Alignment::CENTER)
+    }
+}
+
+impl Layouter for Align {
+    fn constraints_for_child(
+        &self,
         _tree: &'static WidgetTree,
         _index: NodeIndex,
         ctx: LayouterConstrainChildrenCtx,
     ) -> Constraints {
-        ctx.self_constraints
+        ctx.self_constraints.loosen()
     }
 
     fn position_for_child(
@@ -1099,10 +3799,9 @@ impl Layouter for CenteredLayouter {
             parent_index: index,
             child_n: ctx.child_n,
         }));
-        Point::new(
-            (self_size.width - child_size.width) / 2.0,
-            (self_size.height - child_size.height) / 2.0,
-        )
+        self.alignment
+            .resolve(tree.text_direction())
+            .position(self_size, child_size)
     }
 
     fn size_for_self(
@@ -1111,36 +3810,3094 @@ impl Layouter for CenteredLayouter {
         index: NodeIndex,
         ctx: LayouterSizeSelfCtx,
     ) -> Size {
-        // For a centered layouter, the self size could be the size of its child
-        // or it could be determined differently depending on context or other constraints.
-        // Here, we use the maximum constraints as a default.
-        let child_indices = tree
-            .tree
-            .borrow()
-            .neighbors_directed(index, petgraph::Direction::Outgoing)
-            .collect::<Vec<_>>();
-        if child_indices.is_empty() {
-            return ctx.constraints.max; //Handle case with no children
+        let first_child = tree.children(index).first().copied();
+        let child_size = first_child
+            .map(|c| tree.query_node_size(c))
+            .unwrap_or(Size::ZERO);
+
+        let width = match self.width_factor {
+            Some(factor) => child_size.width * factor,
+            None if ctx.constraints.max.width.is_finite() => ctx.constraints.max.width,
+            None => child_size.width,
+        };
+        let height = match self.height_factor {
+            Some(factor) => child_size.height * factor,
+            None if ctx.constraints.max.height.is_finite() => ctx.constraints.max.height,
+            None => child_size.height,
+        };
+
+        ctx.constraints.clamp_size(Size::new(width, height))
+    }
+
+    fn min_intrinsic_width(&self, tree: &'static WidgetTree, index: NodeIndex, height: f64) -> f64 {
+        let child = tree
+            .children(index)
+            .first()
+            .map(|&c| tree.query_min_intrinsic_width(c, height));
+        child.unwrap_or(0.0) * self.width_factor.unwrap_or(1.0)
+    }
+
+    fn max_intrinsic_width(&self, tree: &'static WidgetTree, index: NodeIndex, height: f64) -> f64 {
+        let child = tree
+            .children(index)
+            .first()
+            .map(|&c| tree.query_max_intrinsic_width(c, height));
+        child.unwrap_or(0.0) * self.width_factor.unwrap_or(1.0)
+    }
+
+    fn min_intrinsic_height(&self, tree: &'static WidgetTree, index: NodeIndex, width: f64) -> f64 {
+        let child = tree
+            .children(index)
+            .first()
+            .map(|&c| tree.query_min_intrinsic_height(c, width));
+        child.unwrap_or(0.0) * self.height_factor.unwrap_or(1.0)
+    }
+
+    fn max_intrinsic_height(&self, tree: &'static WidgetTree, index: NodeIndex, width: f64) -> f64 {
+        let child = tree
+            .children(index)
+            .first()
+            .map(|&c| tree.query_max_intrinsic_height(c, width));
+        child.unwrap_or(0.0) * self.height_factor.unwrap_or(1.0)
+    }
+}
+
+/// Sizes itself to the largest size allowed by its constraints that has the given
+/// width / height `ratio`, and forces its child to exactly that size.
+struct AspectRatio {
+    ratio: f64,
+}
+
+impl AspectRatio {
+    /// The ratio, or `None` if it can't describe a size (zero, negative or not finite), in which
+    /// case the node sizes itself to the smallest size allowed and defers to its child's intrinsics
+    fn ratio(&self) -> Option<f64> {
+        Some(self.ratio).filter(|r| r.is_finite() && *r > 0.0)
+    }
+}
+
+impl Layouter for AspectRatio {
+    fn constraints_for_child(
+        &self,
+        tree: &'static WidgetTree,
+        index: NodeIndex,
+        _ctx: LayouterConstrainChildrenCtx,
+    ) -> Constraints {
+        Constraints::tight(tree.query_node_size(index))
+    }
+
+    fn size_for_self(
+        &self,
+        _tree: &'static WidgetTree,
+        _index: NodeIndex,
+        ctx: LayouterSizeSelfCtx,
+    ) -> Size {
+        let Constraints { min, max } = ctx.constraints;
+
+        let Some(ratio) = self.ratio().filter(|_| !ctx.constraints.is_tight()) else {
+            return ctx.constraints.smallest();
+        };
+
+        // Start from the largest bounded dimension, falling back to the minimum when both are
+        // unbounded, then shrink/grow to fit within the constraints while keeping the ratio.
+        let (width, height) = if max.width.is_finite() {
+            (max.width, max.width / ratio)
+        } else if max.height.is_finite() {
+            (max.height * ratio, max.height)
+        } else {
+            let width = min.width.max(min.height * ratio);
+            (width, width / ratio)
+        };
+
+        ctx.constraints.constrain_aspect(Size::new(width, height))
+    }
+
+    fn min_intrinsic_width(&self, tree: &'static WidgetTree, index: NodeIndex, height: f64) -> f64 {
+        if let Some(ratio) = self.ratio().filter(|_| height.is_finite()) {
+            return height * ratio;
+        }
+        let child = tree
+            .children(index)
+            .first()
+            .map(|&c| tree.query_min_intrinsic_width(c, height));
+        child.unwrap_or(0.0)
+    }
+
+    fn max_intrinsic_width(&self, tree: &'static WidgetTree, index: NodeIndex, height: f64) -> f64 {
+        if let Some(ratio) = self.ratio().filter(|_| height.is_finite()) {
+            return height * ratio;
+        }
+        let child = tree
+            .children(index)
+            .first()
+            .map(|&c| tree.query_max_intrinsic_width(c, height));
+        child.unwrap_or(0.0)
+    }
+
+    fn min_intrinsic_height(&self, tree: &'static WidgetTree, index: NodeIndex, width: f64) -> f64 {
+        if let Some(ratio) = self.ratio().filter(|_| width.is_finite()) {
+            return width / ratio;
         }
+        let child = tree
+            .children(index)
+            .first()
+            .map(|&c| tree.query_min_intrinsic_height(c, width));
+        child.unwrap_or(0.0)
+    }
 
-        let child_index = child_indices[0];
-        let child_size = tree.query_node_size(child_index);
-        ctx.constraints.clamp_size(child_size)
+    fn max_intrinsic_height(&self, tree: &'static WidgetTree, index: NodeIndex, width: f64) -> f64 {
+        if let Some(ratio) = self.ratio().filter(|_| width.is_finite()) {
+            return width / ratio;
+        }
+        let child = tree
+            .children(index)
+            .first()
+            .map(|&c| tree.query_max_intrinsic_height(c, width));
+        child.unwrap_or(0.0)
     }
 }
 
-struct DynamicallySizedBoxLayouter {
-    size: Signal<Size>,
+/// Sizes its child to a fraction of the incoming max constraints. A `None` factor (or an
+/// unbounded max) passes the constraints on that axis through unchanged.
+struct FractionallySized {
+    width_factor: Option<f64>,
+    height_factor: Option<f64>,
+    alignment: Alignment,
 }
 
-impl Layouter for DynamicallySizedBoxLayouter {
+impl FractionallySized {
+    /// Undoes the fraction when reporting a child's intrinsic extent. A zero (or negative) factor
+    /// gives the child no space no matter how large we are, so it contributes nothing.
+    fn unscale(extent: f64, factor: Option<f64>) -> f64 {
+        match factor {
+            Some(factor) if factor > 0.0 => extent / factor,
+            Some(_) => 0.0,
+            None => extent,
+        }
+    }
+
+    fn new(width_factor: Option<f64>, height_factor: Option<f64>) -> Self {
+        FractionallySized {
+            width_factor,
+            height_factor,
+            alignment: Alignment::CENTER,
+        }
+    }
+}
+
+impl Layouter for FractionallySized {
+    fn constraints_for_child(
+        &self,
+        _tree: &'static WidgetTree,
+        _index: NodeIndex,
+        ctx: LayouterConstrainChildrenCtx,
+    ) -> Constraints {
+        let constraints = ctx.self_constraints;
+
+        // A fraction of an unbounded dimension is meaningless, so those are passed through
+        let width = self
+            .width_factor
+            .filter(|_| constraints.has_bounded_width())
+            .map(|factor| constraints.max.width * factor);
+        let height = self
+            .height_factor
+            .filter(|_| constraints.has_bounded_height())
+            .map(|factor| constraints.max.height * factor);
+
+        constraints.tighten(width, height)
+    }
+
+    fn position_for_child(
+        &self,
+        tree: &'static WidgetTree,
+        index: NodeIndex,
+        ctx: LayoutChildWasSizedCtx,
+    ) -> Point {
+        let self_size = tree.query_node_size(index);
+        let child_size = tree.query_node_size(tree.query_nth_child(NthChild {
+            parent_index: index,
+            child_n: ctx.child_n,
+        }));
+        self.alignment
+            .resolve(tree.text_direction())
+            .position(self_size, child_size)
+    }
+
+    fn size_for_self(
+        &self,
+        tree: &'static WidgetTree,
+        index: NodeIndex,
+        ctx: LayouterSizeSelfCtx,
+    ) -> Size {
+        let first_child = tree.children(index).first().copied();
+
+        match first_child {
+            Some(c) => ctx.constraints.clamp_size(tree.query_node_size(c)),
+            None => {
+                let max = ctx.constraints.max;
+                let width = match self.width_factor {
+                    Some(factor) if max.width.is_finite() => max.width * factor,
+                    _ => 0.0,
+                };
+                let height = match self.height_factor {
+                    Some(factor) if max.height.is_finite() => max.height * factor,
+                    _ => 0.0,
+                };
+                ctx.constraints.clamp_size(Size::new(width, height))
+            }
+        }
+    }
+
+    fn min_intrinsic_width(&self, tree: &'static WidgetTree, index: NodeIndex, height: f64) -> f64 {
+        let child = tree
+            .children(index)
+            .first()
+            .map(|&c| tree.query_min_intrinsic_width(c, height));
+        Self::unscale(child.unwrap_or(0.0), self.width_factor)
+    }
+
+    fn max_intrinsic_width(&self, tree: &'static WidgetTree, index: NodeIndex, height: f64) -> f64 {
+        let child = tree
+            .children(index)
+            .first()
+            .map(|&c| tree.query_max_intrinsic_width(c, height));
+        Self::unscale(child.unwrap_or(0.0), self.width_factor)
+    }
+
+    fn min_intrinsic_height(&self, tree: &'static WidgetTree, index: NodeIndex, width: f64) -> f64 {
+        let child = tree
+            .children(index)
+            .first()
+            .map(|&c| tree.query_min_intrinsic_height(c, width));
+        Self::unscale(child.unwrap_or(0.0), self.height_factor)
+    }
+
+    fn max_intrinsic_height(&self, tree: &'static WidgetTree, index: NodeIndex, width: f64) -> f64 {
+        let child = tree
+            .children(index)
+            .first()
+            .map(|&c| tree.query_max_intrinsic_height(c, width));
+        Self::unscale(child.unwrap_or(0.0), self.height_factor)
+    }
+}
+
+/// Places a child of a `Stack` at fixed insets from the stack's edges instead of aligning it.
+/// Setting both `left` and `right` (or `top` and `bottom`) stretches the child between them.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+struct Positioned {
+    top: Option<f64>,
+    left: Option<f64>,
+    right: Option<f64>,
+    bottom: Option<f64>,
+    width: Option<f64>,
+    height: Option<f64>,
+}
+
+impl Positioned {
+    fn fill() -> Self {
+        Positioned {
+            top: Some(0.0),
+            left: Some(0.0),
+            right: Some(0.0),
+            bottom: Some(0.0),
+            ..Default::default()
+        }
+    }
+
+    /// The extent the child is pinned to along one axis given the stack's extent on that axis,
+    /// or `None` if it's free to be any size
+    fn axis_extent(
+        start: Option<f64>,
+        end: Option<f64>,
+        extent: Option<f64>,
+        stack_extent: f64,
+    ) -> Option<f64> {
+        match (start, end, extent) {
+            (Some(start), Some(end), _) => Some((stack_extent - start - end).max(0.0)),
+            (_, _, extent) => extent,
+        }
+    }
+
+    /// Offset along one axis, or `None` if neither inset is set and the stack's alignment
+    /// should be used instead
+    fn axis_position(
+        start: Option<f64>,
+        end: Option<f64>,
+        child_extent: f64,
+        stack_extent: f64,
+    ) -> Option<f64> {
+        match (start, end) {
+            (Some(start), _) => Some(start),
+            (None, Some(end)) => Some(stack_extent - end - child_extent),
+            (None, None) => None,
+        }
+    }
+}
+
+impl Layouter for Positioned {
+    fn parent_data(&self) -> Option<&dyn Any> {
+        Some(self)
+    }
+}
+
+/// Overlays its children on top of each other. Children paint in child order, so later children
+/// paint on top of earlier ones.
+///
+/// The stack sizes itself to its largest non-`Positioned` child and places those children
+/// according to `alignment`. `Positioned` children don't affect the stack's size and are placed
+/// relative to its edges.
+struct Stack {
+    alignment: Alignment,
+}
+
+impl Stack {
+    fn new(alignment: Alignment) -> Self {
+        Stack { alignment }
+    }
+
+    fn non_positioned_children(
+        &self,
+        tree: &'static WidgetTree,
+        index: NodeIndex,
+    ) -> impl Iterator<Item = NodeIndex> {
+        tree.children(index)
+            .into_iter()
+            .filter(move |&c| tree.parent_data::<Positioned>(c).is_none())
+    }
+}
+
+impl Layouter for Stack {
+    fn constraints_for_child(
+        &self,
+        tree: &'static WidgetTree,
+        index: NodeIndex,
+        ctx: LayouterConstrainChildrenCtx,
+    ) -> Constraints {
+        let child = tree.query_nth_child(NthChild {
+            parent_index: index,
+            child_n: ctx.child_n,
+        });
+
+        let Some(positioned) = tree.parent_data::<Positioned>(child) else {
+            return ctx.self_constraints.loosen();
+        };
+
+        let self_size = tree.query_node_size(index);
+        let width = Positioned::axis_extent(
+            positioned.left,
+            positioned.right,
+            positioned.width,
+            self_size.width,
+        );
+        let height = Positioned::axis_extent(
+            positioned.top,
+            positioned.bottom,
+            positioned.height,
+            self_size.height,
+        );
+
+        Constraints::tight_for(width, height)
+    }
+
+    fn position_for_child(
+        &self,
+        tree: &'static WidgetTree,
+        index: NodeIndex,
+        ctx: LayoutChildWasSizedCtx,
+    ) -> Point {
+        let child = tree.query_nth_child(NthChild {
+            parent_index: index,
+            child_n: ctx.child_n,
+        });
+        let self_size = tree.query_node_size(index);
+        let child_size = tree.query_node_size(child);
+        let aligned = self
+            .alignment
+            .resolve(tree.text_direction())
+            .position(self_size, child_size);
+
+        let Some(positioned) = tree.parent_data::<Positioned>(child) else {
+            return aligned;
+        };
+
+        Point::new(
+            Positioned::axis_position(
+                positioned.left,
+                positioned.right,
+                child_size.width,
+                self_size.width,
+            )
+            .unwrap_or(aligned.x),
+            Positioned::axis_position(
+                positioned.top,
+                positioned.bottom,
+                child_size.height,
+                self_size.height,
+            )
+            .unwrap_or(aligned.y),
+        )
+    }
+
+    fn size_for_self(
+        &self,
+        tree: &'static WidgetTree,
+        index: NodeIndex,
+        ctx: LayouterSizeSelfCtx,
+    ) -> Size {
+        let mut largest: Option<Size> = None;
+        for child in self.non_positioned_children(tree, index) {
+            let child_size = tree.query_node_size(child);
+            let largest = largest.get_or_insert(Size::ZERO);
+            largest.width = largest.width.max(child_size.width);
+            largest.height = largest.height.max(child_size.height);
+        }
+
+        // With only positioned children, there is nothing to shrink-wrap, so take up as much
+        // space as we're allowed to
+        let size = largest.unwrap_or_else(|| {
+            let Constraints { min, max } = ctx.constraints;
+            Size::new(
+                if max.width.is_finite() { max.width } else { min.width },
+                if max.height.is_finite() { max.height } else { min.height },
+            )
+        });
+
+        ctx.constraints.clamp_size(size)
+    }
+
+    fn min_intrinsic_width(&self, tree: &'static WidgetTree, index: NodeIndex, height: f64) -> f64 {
+        self.non_positioned_children(tree, index)
+            .map(|c| tree.query_min_intrinsic_width(c, height))
+            .fold(0.0, f64::max)
+    }
+
+    fn max_intrinsic_width(&self, tree: &'static WidgetTree, index: NodeIndex, height: f64) -> f64 {
+        self.non_positioned_children(tree, index)
+            .map(|c| tree.query_max_intrinsic_width(c, height))
+            .fold(0.0, f64::max)
+    }
+
+    fn min_intrinsic_height(&self, tree: &'static WidgetTree, index: NodeIndex, width: f64) -> f64 {
+        self.non_positioned_children(tree, index)
+            .map(|c| tree.query_min_intrinsic_height(c, width))
+            .fold(0.0, f64::max)
+    }
+
+    fn max_intrinsic_height(&self, tree: &'static WidgetTree, index: NodeIndex, width: f64) -> f64 {
+        self.non_positioned_children(tree, index)
+            .map(|c| tree.query_max_intrinsic_height(c, width))
+            .fold(0.0, f64::max)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum GridTrack {
+    /// Exactly this many pixels
+    Fixed(f64),
+    /// A share of the space left over once all other tracks are sized. Behaves like `Auto` when
+    /// the grid is unbounded on this axis.
+    Fraction(f64),
+    /// As large as the largest child that sits only in this track
+    Auto,
+    /// Sized like `Auto`, but never smaller than the first value or larger than the second
+    MinMax(f64, f64),
+}
+
+impl GridTrack {
+    fn is_content_sized(&self, available: f64) -> bool {
+        match self {
+            GridTrack::Fixed(_) => false,
+            GridTrack::Fraction(_) => !available.is_finite(),
+            GridTrack::Auto | GridTrack::MinMax(_, _) => true,
+        }
+    }
+}
+
+/// Where a child of a `Grid` goes. A `None` row or column is filled in by auto-placement.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct GridItem {
+    row: Option<usize>,
+    column: Option<usize>,
+    row_span: usize,
+    column_span: usize,
+}
+
+impl Default for GridItem {
+    fn default() -> Self {
+        GridItem {
+            row: None,
+            column: None,
+            row_span: 1,
+            column_span: 1,
+        }
+    }
+}
+
+impl GridItem {
+    fn at(row: usize, column: usize) -> Self {
+        GridItem {
+            row: Some(row),
+            column: Some(column),
+            ..Default::default()
+        }
+    }
+
+    fn spanning(self, row_span: usize, column_span: usize) -> Self {
+        GridItem {
+            row_span,
+            column_span,
+            ..self
+        }
+    }
+}
+
+impl Layouter for GridItem {
+    fn parent_data(&self) -> Option<&dyn Any> {
+        Some(self)
+    }
+}
+
+/// A layout that places children on rows and columns of tracks with gaps between them
+trait TrackLayout {
+    /// Space between adjacent tracks along `axis`
+    fn gap(&self, axis: Axis) -> f64;
+
+    /// Offset of the start of `track`, including gaps
+    fn track_offset(&self, sizes: &[f64], axis: Axis, track: usize) -> f64 {
+        sizes[..track].iter().sum::<f64>() + self.gap(axis) * track as f64
+    }
+
+    /// Total extent of a span of tracks, including the gaps inside it
+    fn span_extent(&self, sizes: &[f64], axis: Axis, span: std::ops::Range<usize>) -> f64 {
+        let gaps = self.gap(axis) * span.len().saturating_sub(1) as f64;
+        sizes[span].iter().sum::<f64>() + gaps
+    }
+}
+
+/// The resolved cell of a grid child, after auto-placement
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct GridCell {
+    row: usize,
+    column: usize,
+    row_span: usize,
+    column_span: usize,
+}
+
+impl GridCell {
+    fn span(&self, axis: Axis) -> std::ops::Range<usize> {
+        match axis {
+            Axis::Horizontal => self.column..self.column + self.column_span,
+            Axis::Vertical => self.row..self.row + self.row_span,
+        }
+    }
+}
+
+/// Lays children out in rows and columns. Children are placed with a `GridItem` wrapper, and
+/// any child without an explicit cell is auto-placed into the next free cell, row by row. Rows
+/// past the end of `rows` are sized with `implicit_row`.
+///
+/// Children that sit in a content-sized track (`Auto`, `MinMax`, or `Fraction` when unbounded)
+/// are given loose constraints and measured to size those tracks, so they may not fill their
+/// cell. Every other child is sized to exactly fill its cell.
+struct Grid {
+    columns: Vec<GridTrack>,
+    rows: Vec<GridTrack>,
+    implicit_row: GridTrack,
+    column_gap: f64,
+    row_gap: f64,
+    /// When the children were last changed as of the last placement, and where the cells went.
+    /// Placement only depends on the children and their `GridItem`s, so it's done once per change
+    /// to the children rather than on every query.
+    placement: RefCell<Option<(usize, Rc<Vec<GridCell>>)>>,
+}
+
+impl Grid {
+    fn new(columns: Vec<GridTrack>, rows: Vec<GridTrack>) -> Self {
+        Grid {
+            columns,
+            rows,
+            implicit_row: GridTrack::Auto,
+            column_gap: 0.0,
+            row_gap: 0.0,
+            placement: RefCell::new(None),
+        }
+    }
+
+    /// A grid without any columns has a single `Auto` one
+    fn tracks(&self, axis: Axis, cells: &[GridCell]) -> Vec<GridTrack> {
+        match axis {
+            Axis::Horizontal if self.columns.is_empty() => vec![GridTrack::Auto],
+            Axis::Horizontal => self.columns.clone(),
+            Axis::Vertical => {
+                let row_count = cells
+                    .iter()
+                    .map(|c| c.row + c.row_span)
+                    .max()
+                    .unwrap_or(0)
+                    .max(self.rows.len());
+                (0..row_count)
+                    .map(|r| self.rows.get(r).copied().unwrap_or(self.implicit_row))
+                    .collect()
+            }
+        }
+    }
+
+    /// Resolves the cell of every child, in child order
+    fn cells(&self, tree: &'static WidgetTree, index: NodeIndex) -> Rc<Vec<GridCell>> {
+        let children_revision = tree.children_revision(index);
+        if let Some((placed, cells)) = &*self.placement.borrow() {
+            if *placed == children_revision {
+                return cells.clone();
+            }
+        }
+
+        let items = tree
+            .children(index)
+            .into_iter()
+            .map(|c| tree.parent_data::<GridItem>(c).unwrap_or_default())
+            .collect::<Vec<_>>();
+        let cells = Rc::new(self.place(&items));
+        *self.placement.borrow_mut() = Some((children_revision, cells.clone()));
+        cells
+    }
+
+    /// Auto-places `items` around the explicitly placed ones
+    fn place(&self, items: &[GridItem]) -> Vec<GridCell> {
+        let column_count = self.columns.len().max(1);
+
+        let mut occupied = HashSet::new();
+        let fits = |occupied: &HashSet<(usize, usize)>, cell: &GridCell| {
+            cell.column + cell.column_span <= column_count
+                && cell.span(Axis::Vertical).all(|r| {
+                    cell.span(Axis::Horizontal)
+                        .all(|c| !occupied.contains(&(r, c)))
+                })
+        };
+        let occupy = |occupied: &mut HashSet<(usize, usize)>, cell: &GridCell| {
+            for r in cell.span(Axis::Vertical) {
+                for c in cell.span(Axis::Horizontal) {
+                    occupied.insert((r, c));
+                }
+            }
+        };
+        let cell_for = |item: &GridItem, row: usize, column: usize| {
+            let column_span = item.column_span.clamp(1, column_count);
+            GridCell {
+                row,
+                column: column.min(column_count - column_span),
+                row_span: item.row_span.max(1),
+                column_span,
+            }
+        };
+
+        // Explicitly placed children claim their cells first, then everything else fills in
+        // around them in child order
+        let mut cells = vec![None; items.len()];
+        for (n, item) in items.iter().enumerate() {
+            if let (Some(row), Some(column)) = (item.row, item.column) {
+                let cell = cell_for(item, row, column);
+                occupy(&mut occupied, &cell);
+                cells[n] = Some(cell);
+            }
+        }
+
+        let mut cursor = (0, 0);
+        for (n, item) in items.iter().enumerate() {
+            if cells[n].is_some() {
+                continue;
+            }
+
+            let cell = match (item.row, item.column) {
+                (Some(row), _) => (0..column_count)
+                    .map(|column| cell_for(item, row, column))
+                    .find(|cell| fits(&occupied, cell))
+                    .unwrap_or_else(|| cell_for(item, row, 0)),
+                (None, Some(column)) => (0..)
+                    .map(|row| cell_for(item, row, column))
+                    .find(|cell| fits(&occupied, cell))
+                    .unwrap(),
+                (None, None) => {
+                    let (mut row, mut column) = cursor;
+                    loop {
+                        if column >= column_count {
+                            row += 1;
+                            column = 0;
+                        }
+                        let cell = cell_for(item, row, column);
+                        if cell.column == column && fits(&occupied, &cell) {
+                            cursor = (row, column + cell.column_span);
+                            break cell;
+                        }
+                        column += 1;
+                    }
+                }
+            };
+
+            occupy(&mut occupied, &cell);
+            cells[n] = Some(cell);
+        }
+
+        cells.into_iter().map(|c| c.unwrap()).collect()
+    }
+
+    /// Children in a content-sized track on either axis are measured rather than stretched
+    fn is_content_measured(&self, cell: &GridCell, cells: &[GridCell], available: Size) -> bool {
+        [Axis::Horizontal, Axis::Vertical].into_iter().any(|axis| {
+            let tracks = self.tracks(axis, cells);
+            cell.span(axis)
+                .any(|t| tracks[t].is_content_sized(axis.main(available)))
+        })
+    }
+
+    fn track_sizes(
+        &self,
+        tree: &'static WidgetTree,
+        index: NodeIndex,
+        axis: Axis,
+        cells: &[GridCell],
+        available: Size,
+    ) -> Vec<f64> {
+        let tracks = self.tracks(axis, cells);
+        let available_extent = axis.main(available);
+
+        let mut sizes = tracks
+            .iter()
+            .map(|t| match t {
+                GridTrack::Fixed(px) => *px,
+                _ => 0.0,
+            })
+            .collect::<Vec<_>>();
+
+        // Content-sized tracks grow to fit the children that sit only in them. Those children
+        // got loose constraints that don't depend on any track size, so this can't cycle.
+        for (child_n, cell) in cells.iter().enumerate() {
+            let span = cell.span(axis);
+            if span.len() != 1 || !tracks[span.start].is_content_sized(available_extent) {
+                continue;
+            }
+
+            let child = tree.query_nth_child(NthChild {
+                parent_index: index,
+                child_n,
+            });
+            let extent = axis.main(tree.query_node_size(child));
+            sizes[span.start] = sizes[span.start].max(extent);
+        }
+
+        for (size, track) in sizes.iter_mut().zip(&tracks) {
+            if let GridTrack::MinMax(min, max) = track {
+                *size = size.max(*min).min(*max);
+            }
+        }
+
+        if available_extent.is_finite() {
+            let total_fraction = tracks
+                .iter()
+                .map(|t| match t {
+                    GridTrack::Fraction(f) => *f,
+                    _ => 0.0,
+                })
+                .sum::<f64>();
+
+            if total_fraction > 0.0 {
+                let gaps = self.gap(axis) * tracks.len().saturating_sub(1) as f64;
+                let used = sizes.iter().sum::<f64>() + gaps;
+                let leftover = (available_extent - used).max(0.0);
+
+                for (size, track) in sizes.iter_mut().zip(&tracks) {
+                    if let GridTrack::Fraction(f) = track {
+                        *size = leftover * f / total_fraction;
+                    }
+                }
+            }
+        }
+
+        sizes
+    }
+
+    /// Intrinsic extent of the grid along `axis`, treating every non-fixed track as
+    /// content-sized. `measure` gives a child's intrinsic extent along `axis`.
+    fn intrinsic_extent(
+        &self,
+        tree: &'static WidgetTree,
+        index: NodeIndex,
+        axis: Axis,
+        measure: impl Fn(NodeIndex) -> f64,
+    ) -> f64 {
+        let cells = self.cells(tree, index);
+        let tracks = self.tracks(axis, &cells);
+
+        let mut sizes = tracks
+            .iter()
+            .map(|t| match t {
+                GridTrack::Fixed(px) => *px,
+                _ => 0.0,
+            })
+            .collect::<Vec<_>>();
+
+        for (child, cell) in tree.children(index).into_iter().zip(cells.iter()) {
+            let span = cell.span(axis);
+            if span.len() != 1 || matches!(tracks[span.start], GridTrack::Fixed(_)) {
+                continue;
+            }
+            sizes[span.start] = sizes[span.start].max(measure(child));
+        }
+
+        for (size, track) in sizes.iter_mut().zip(&tracks) {
+            if let GridTrack::MinMax(min, max) = track {
+                *size = size.max(*min).min(*max);
+            }
+        }
+
+        self.span_extent(&sizes, axis, 0..sizes.len())
+    }
+}
+
+impl TrackLayout for Grid {
+    fn gap(&self, axis: Axis) -> f64 {
+        match axis {
+            Axis::Horizontal => self.column_gap,
+            Axis::Vertical => self.row_gap,
+        }
+    }
+}
+
+impl Layouter for Grid {
+    fn constraints_for_child(
+        &self,
+        tree: &'static WidgetTree,
+        index: NodeIndex,
+        ctx: LayouterConstrainChildrenCtx,
+    ) -> Constraints {
+        let cells = self.cells(tree, index);
+        let cell = cells[ctx.child_n];
+        let available = ctx.self_constraints.max;
+        let content_measured = self.is_content_measured(&cell, &cells, available);
+
+        // How much room the child gets along `axis`, and whether it has to fill it
+        let axis_extent = |axis: Axis| {
+            let tracks = self.tracks(axis, &cells);
+            let span = cell.span(axis);
+
+            if span.clone().all(|t| matches!(tracks[t], GridTrack::Fixed(_))) {
+                let sizes = tracks
+                    .iter()
+                    .map(|t| match t {
+                        GridTrack::Fixed(px) => *px,
+                        _ => 0.0,
+                    })
+                    .collect::<Vec<_>>();
+                (self.span_extent(&sizes, axis, span), !content_measured)
+            } else if content_measured {
+                (axis.main(available), false)
+            } else {
+                let sizes = self.track_sizes(tree, index, axis, &cells, available);
+                (self.span_extent(&sizes, axis, span), true)
+            }
+        };
+
+        let (width, tight_width) = axis_extent(Axis::Horizontal);
+        let (height, tight_height) = axis_extent(Axis::Vertical);
+
+        Constraints::loose(Size::new(width, height))
+            .tighten(tight_width.then_some(width), tight_height.then_some(height))
+    }
+
+    fn position_for_child(
+        &self,
+        tree: &'static WidgetTree,
+        index: NodeIndex,
+        ctx: LayoutChildWasSizedCtx,
+    ) -> Point {
+        let cells = self.cells(tree, index);
+        let cell = cells[ctx.child_n];
+        let available = tree.query_node_constraints(index).max;
+
+        let column_sizes = self.track_sizes(tree, index, Axis::Horizontal, &cells, available);
+        let row_sizes = self.track_sizes(tree, index, Axis::Vertical, &cells, available);
+
+        let x = self.track_offset(&column_sizes, Axis::Horizontal, cell.column);
+        let y = self.track_offset(&row_sizes, Axis::Vertical, cell.row);
+
+        // Right-to-left grids number their columns from the right, and children sit against
+        // the right edge of their cell
+        match tree.text_direction() {
+            TextDirection::Ltr => Point::new(x, y),
+            TextDirection::Rtl => {
+                let child = tree.query_nth_child(NthChild {
+                    parent_index: index,
+                    child_n: ctx.child_n,
+                });
+                let child_width = tree.query_node_size(child).width;
+                Point::new(tree.query_node_size(index).width - x - child_width, y)
+            }
+        }
+    }
+
+    fn size_for_self(
+        &self,
+        tree: &'static WidgetTree,
+        index: NodeIndex,
+        ctx: LayouterSizeSelfCtx,
+    ) -> Size {
+        let cells = self.cells(tree, index);
+        let available = ctx.constraints.max;
+
+        let column_sizes = self.track_sizes(tree, index, Axis::Horizontal, &cells, available);
+        let row_sizes = self.track_sizes(tree, index, Axis::Vertical, &cells, available);
+
+        ctx.constraints.clamp_size(Size::new(
+            self.span_extent(&column_sizes, Axis::Horizontal, 0..column_sizes.len()),
+            self.span_extent(&row_sizes, Axis::Vertical, 0..row_sizes.len()),
+        ))
+    }
+
+    fn min_intrinsic_width(&self, tree: &'static WidgetTree, index: NodeIndex, height: f64) -> f64 {
+        self.intrinsic_extent(tree, index, Axis::Horizontal, |c| {
+            tree.query_min_intrinsic_width(c, height)
+        })
+    }
+
+    fn max_intrinsic_width(&self, tree: &'static WidgetTree, index: NodeIndex, height: f64) -> f64 {
+        self.intrinsic_extent(tree, index, Axis::Horizontal, |c| {
+            tree.query_max_intrinsic_width(c, height)
+        })
+    }
+
+    fn min_intrinsic_height(&self, tree: &'static WidgetTree, index: NodeIndex, width: f64) -> f64 {
+        self.intrinsic_extent(tree, index, Axis::Vertical, |c| {
+            tree.query_min_intrinsic_height(c, width)
+        })
+    }
+
+    fn max_intrinsic_height(&self, tree: &'static WidgetTree, index: NodeIndex, width: f64) -> f64 {
+        self.intrinsic_extent(tree, index, Axis::Vertical, |c| {
+            tree.query_max_intrinsic_height(c, width)
+        })
+    }
+}
+
+/// How leftover space along the main axis is distributed between children
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+enum MainAxisAlignment {
+    #[default]
+    Start,
+    End,
+    Center,
+    SpaceBetween,
+    SpaceAround,
+    SpaceEvenly,
+}
+
+impl MainAxisAlignment {
+    /// Returns the offset of the first child and the extra space to put between each child
+    fn distribute(&self, free_space: f64, child_count: usize) -> (f64, f64) {
+        let free_space = free_space.max(0.0);
+        let n = child_count as f64;
+
+        match self {
+            MainAxisAlignment::Start => (0.0, 0.0),
+            MainAxisAlignment::End => (free_space, 0.0),
+            MainAxisAlignment::Center => (free_space / 2.0, 0.0),
+            MainAxisAlignment::SpaceBetween if child_count > 1 => {
+                (0.0, free_space / (n - 1.0))
+            }
+            MainAxisAlignment::SpaceBetween => (0.0, 0.0),
+            MainAxisAlignment::SpaceAround if child_count > 0 => {
+                (free_space / n / 2.0, free_space / n)
+            }
+            MainAxisAlignment::SpaceAround => (0.0, 0.0),
+            MainAxisAlignment::SpaceEvenly => {
+                (free_space / (n + 1.0), free_space / (n + 1.0))
+            }
+        }
+    }
+}
+
+/// Where children go across the main axis when they're smaller than the space they're given
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+enum CrossAxisAlignment {
+    #[default]
+    Start,
+    End,
+    Center,
+    /// Line up the first baselines of children that have one. Children without a baseline are
+    /// placed at the start. Only meaningful for horizontal layouts; vertical ones treat it as
+    /// `Start`.
+    Baseline,
+}
+
+impl CrossAxisAlignment {
+    fn offset(&self, free_space: f64) -> f64 {
+        match self {
+            CrossAxisAlignment::Start | CrossAxisAlignment::Baseline => 0.0,
+            CrossAxisAlignment::End => free_space,
+            CrossAxisAlignment::Center => free_space / 2.0,
+        }
+    }
+}
+
+/// A line of children in a `Flow`
+#[derive(Clone, Debug, PartialEq)]
+struct FlowRun {
+    /// Child indices `first..first + count` are in this run
+    first: usize,
+    count: usize,
+    main_extent: f64,
+    cross_extent: f64,
+    /// Distance from the top of the run to the baseline children are aligned to, when using
+    /// `CrossAxisAlignment::Baseline`
+    baseline: f64,
+}
+
+/// Places children one after another along `axis`, starting a new run whenever the next child
+/// would go past the max constraints.
+struct Flow {
+    axis: Axis,
+    /// Space between children in the same run
+    spacing: f64,
+    /// Space between runs
+    run_spacing: f64,
+    /// How each run distributes its leftover space along the main axis
+    alignment: MainAxisAlignment,
+    /// Where children go within the cross extent of their run
+    cross_axis_alignment: CrossAxisAlignment,
+}
+
+impl Flow {
+    fn new(axis: Axis) -> Self {
+        Flow {
+            axis,
+            spacing: 0.0,
+            run_spacing: 0.0,
+            alignment: MainAxisAlignment::Start,
+            cross_axis_alignment: CrossAxisAlignment::Start,
+        }
+    }
+
+    fn runs(
+        &self,
+        tree: &'static WidgetTree,
+        index: NodeIndex,
+        constraints: Constraints,
+    ) -> Vec<FlowRun> {
+        let max_main = self.axis.main(constraints.max);
+
+        let mut runs: Vec<FlowRun> = Vec::new();
+        for (child_n, child) in tree.children(index).into_iter().enumerate() {
+            let child_size = tree.query_node_size(child);
+            let child_main = self.axis.main(child_size);
+            let child_cross = self.axis.cross(child_size);
+
+            match runs.last_mut() {
+                Some(run) if run.main_extent + self.spacing + child_main <= max_main => {
+                    run.count += 1;
+                    run.main_extent += self.spacing + child_main;
+                    run.cross_extent = run.cross_extent.max(child_cross);
+                }
+                _ => runs.push(FlowRun {
+                    first: child_n,
+                    count: 1,
+                    main_extent: child_main,
+                    cross_extent: child_cross,
+                    baseline: 0.0,
+                }),
+            }
+        }
+
+        if self.aligns_baselines() {
+            let children = tree.children(index);
+            for run in runs.iter_mut() {
+                let children = &children[run.first..run.first + run.count];
+
+                run.baseline = children
+                    .iter()
+                    .filter_map(|&c| tree.query_node_baseline(c))
+                    .fold(0.0, f64::max);
+                run.cross_extent = children
+                    .iter()
+                    .map(|&c| {
+                        let offset = tree
+                            .query_node_baseline(c)
+                            .map_or(0.0, |baseline| run.baseline - baseline);
+                        offset + tree.query_node_size(c).height
+                    })
+                    .fold(0.0, f64::max);
+            }
+        }
+
+        runs
+    }
+
+    fn aligns_baselines(&self) -> bool {
+        self.axis == Axis::Horizontal && self.cross_axis_alignment == CrossAxisAlignment::Baseline
+    }
+
+    /// Intrinsic extent along the main axis: the widest child if `min`, otherwise everything in
+    /// one run
+    fn intrinsic_main(&self, tree: &'static WidgetTree, index: NodeIndex, min: bool) -> f64 {
+        let extents = tree
+            .children(index)
+            .into_iter()
+            .map(|c| match (self.axis, min) {
+                (Axis::Horizontal, true) => tree.query_min_intrinsic_width(c, f64::INFINITY),
+                (Axis::Horizontal, false) => tree.query_max_intrinsic_width(c, f64::INFINITY),
+                (Axis::Vertical, true) => tree.query_min_intrinsic_height(c, f64::INFINITY),
+                (Axis::Vertical, false) => tree.query_max_intrinsic_height(c, f64::INFINITY),
+            })
+            .collect::<Vec<_>>();
+
+        if min {
+            extents.into_iter().fold(0.0, f64::max)
+        } else {
+            extents.iter().sum::<f64>() + self.spacing * extents.len().saturating_sub(1) as f64
+        }
+    }
+
+    /// Intrinsic extent across the main axis when the flow is `main` long, by breaking the
+    /// children's intrinsic sizes into runs the same way layout would
+    fn intrinsic_cross(&self, tree: &'static WidgetTree, index: NodeIndex, main: f64) -> f64 {
+        let mut runs: Vec<(f64, f64)> = Vec::new();
+        for c in tree.children(index) {
+            let (child_main, child_cross) = match self.axis {
+                Axis::Horizontal => {
+                    let width = tree.query_max_intrinsic_width(c, f64::INFINITY).min(main);
+                    (width, tree.query_max_intrinsic_height(c, width))
+                }
+                Axis::Vertical => {
+                    let height = tree.query_max_intrinsic_height(c, f64::INFINITY).min(main);
+                    (height, tree.query_max_intrinsic_width(c, height))
+                }
+            };
+
+            match runs.last_mut() {
+                Some((run_main, run_cross)) if *run_main + self.spacing + child_main <= main => {
+                    *run_main += self.spacing + child_main;
+                    *run_cross = run_cross.max(child_cross);
+                }
+                _ => runs.push((child_main, child_cross)),
+            }
+        }
+
+        runs.iter().map(|(_, cross)| cross).sum::<f64>()
+            + self.run_spacing * runs.len().saturating_sub(1) as f64
+    }
+}
+
+impl Layouter for Flow {
+    fn constraints_for_child(
+        &self,
+        _tree: &'static WidgetTree,
+        _index: NodeIndex,
+        ctx: LayouterConstrainChildrenCtx,
+    ) -> Constraints {
+        Constraints::loose(
+            self.axis
+                .size(self.axis.main(ctx.self_constraints.max), f64::INFINITY),
+        )
+    }
+
+    fn position_for_child(
+        &self,
+        tree: &'static WidgetTree,
+        index: NodeIndex,
+        ctx: LayoutChildWasSizedCtx,
+    ) -> Point {
+        let constraints = tree.query_node_constraints(index);
+        let self_size = tree.query_node_size(index);
+        let runs = self.runs(tree, index, constraints);
+
+        let mut cross = 0.0;
+        for run in runs {
+            if ctx.child_n >= run.first + run.count {
+                cross += run.cross_extent + self.run_spacing;
+                continue;
+            }
+
+            let (mut main, between) = self
+                .alignment
+                .distribute(self.axis.main(self_size) - run.main_extent, run.count);
+
+            for child_n in run.first..run.first + run.count {
+                let child_size = tree.query_node_size(tree.query_nth_child(NthChild {
+                    parent_index: index,
+                    child_n,
+                }));
+
+                if child_n == ctx.child_n {
+                    let baseline_offset = if self.aligns_baselines() {
+                        tree.query_node_baseline(tree.query_nth_child(NthChild {
+                            parent_index: index,
+                            child_n,
+                        }))
+                        .map(|baseline| run.baseline - baseline)
+                    } else {
+                        None
+                    };
+                    let cross_offset = baseline_offset.unwrap_or_else(|| {
+                        self.cross_axis_alignment
+                            .offset(run.cross_extent - self.axis.cross(child_size))
+                    });
+                    let position = self.axis.point(main, cross + cross_offset);
+
+                    // Right-to-left flows fill runs from the right, whichever axis they run along
+                    return match tree.text_direction() {
+                        TextDirection::Ltr => position,
+                        TextDirection::Rtl => {
+                            Point::new(self_size.width - position.x - child_size.width, position.y)
+                        }
+                    };
+                }
+
+                main += self.axis.main(child_size) + self.spacing + between;
+            }
+        }
+
+        unreachable!("child {} is not in any run", ctx.child_n)
+    }
+
+    fn size_for_self(
+        &self,
+        tree: &'static WidgetTree,
+        index: NodeIndex,
+        ctx: LayouterSizeSelfCtx,
+    ) -> Size {
+        let runs = self.runs(tree, index, ctx.constraints);
+
+        let main = runs.iter().map(|r| r.main_extent).fold(0.0, f64::max);
+        let cross = runs.iter().map(|r| r.cross_extent).sum::<f64>()
+            + self.run_spacing * runs.len().saturating_sub(1) as f64;
+
+        ctx.constraints.clamp_size(self.axis.size(main, cross))
+    }
+
+    fn min_intrinsic_width(&self, tree: &'static WidgetTree, index: NodeIndex, height: f64) -> f64 {
+        match self.axis {
+            Axis::Horizontal => self.intrinsic_main(tree, index, true),
+            Axis::Vertical => self.intrinsic_cross(tree, index, height),
+        }
+    }
+
+    fn max_intrinsic_width(&self, tree: &'static WidgetTree, index: NodeIndex, height: f64) -> f64 {
+        match self.axis {
+            Axis::Horizontal => self.intrinsic_main(tree, index, false),
+            Axis::Vertical => self.intrinsic_cross(tree, index, height),
+        }
+    }
+
+    fn min_intrinsic_height(&self, tree: &'static WidgetTree, index: NodeIndex, width: f64) -> f64 {
+        match self.axis {
+            Axis::Horizontal => self.intrinsic_cross(tree, index, width),
+            Axis::Vertical => self.intrinsic_main(tree, index, true),
+        }
+    }
+
+    fn max_intrinsic_height(&self, tree: &'static WidgetTree, index: NodeIndex, width: f64) -> f64 {
+        match self.axis {
+            Axis::Horizontal => self.intrinsic_cross(tree, index, width),
+            Axis::Vertical => self.intrinsic_main(tree, index, false),
+        }
+    }
+}
+
+/// Clips its child's drawing, and hit testing, to its bounds with corners rounded by `radius`
+struct Clip {
+    radius: f64,
+}
+
+impl Clip {
+    fn rect() -> Self {
+        Clip { radius: 0.0 }
+    }
+
+    fn rounded(radius: f64) -> Self {
+        Clip { radius }
+    }
+}
+
+impl Layouter for Clip {
+    fn layer(&self, tree: &'static WidgetTree, index: NodeIndex) -> Option<Layer> {
+        let rect = Rect::from_origin_size(Point::ORIGIN, tree.query_node_size(index));
+        Some(Layer::clip(RoundedRect::from_rect(rect, self.radius)))
+    }
+}
+
+/// Draws its child faded by `opacity`, from 0.0 (invisible) to 1.0 (unchanged). The child is
+/// composited as a whole, so overlapping parts of it don't show through each other.
+struct Opacity {
+    opacity: MaybeSignal<f64>,
+}
+
+impl Layouter for Opacity {
+    fn layer(&self, tree: &'static WidgetTree, _index: NodeIndex) -> Option<Layer> {
+        let opacity = self.opacity.get(tree).clamp(0.0, 1.0);
+        (opacity < 1.0).then(|| Layer::alpha(opacity as f32))
+    }
+}
+
+/// Blends its child with whatever was drawn underneath it using `blend`, e.g. `Mix::Multiply`
+struct BlendLayer {
+    blend: BlendMode,
+}
+
+impl Layouter for BlendLayer {
+    fn layer(&self, _tree: &'static WidgetTree, _index: NodeIndex) -> Option<Layer> {
+        Some(Layer::blend(self.blend))
+    }
+}
+
+/// Draws its child into a scene of its own, which is reused as is until something in it changes.
+/// Worth putting around parts of the tree that rarely change, so frames where something else
+/// changes don't visit them.
+struct RepaintBoundary;
+
+impl Layouter for RepaintBoundary {
+    fn is_repaint_boundary(&self) -> bool {
+        true
+    }
+}
+
+/// How far a `ScrollView` is scrolled, and how far it could be
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct ScrollMetrics {
+    /// Extent of the child along the scroll axis
+    content_extent: f64,
+    /// Extent of the visible area along the scroll axis
+    viewport_extent: f64,
+    /// Current scroll offset, clamped to `0.0..=max_offset()`
+    offset: f64,
+}
+
+impl ScrollMetrics {
+    fn max_offset(&self) -> f64 {
+        (self.content_extent - self.viewport_extent).max(0.0)
+    }
+
+    /// Moves `offset` by `delta`, staying in bounds. Returns whether it moved, so a scroll that's
+    /// already at its end can let the next one out have it.
+    fn scroll(&self, tree: &'static WidgetTree, offset: Signal<f64>, delta: f64) -> bool {
+        let scrolled = (self.offset + delta).clamp(0.0, self.max_offset());
+        if scrolled == self.offset {
+            return false;
+        }
+        tree.set_signal(offset, scrolled);
+        true
+    }
+}
+
+/// Shows a window onto a single child that is unbounded along `axis`. The child is shifted by
+/// the value of `offset`, and drawing is clipped to the view's bounds.
+#[derive(Clone, Copy, Debug)]
+struct ScrollView {
+    axis: Axis,
+    offset: Signal<f64>,
+}
+
+impl ScrollView {
+    fn new(axis: Axis, offset: Signal<f64>) -> Self {
+        ScrollView { axis, offset }
+    }
+
+    /// Extents of the scroll view at `index`, e.g. for sizing and positioning a scrollbar
+    fn metrics(&self, tree: &'static WidgetTree, index: NodeIndex) -> ScrollMetrics {
+        let viewport_extent = self.axis.main(tree.query_node_size(index));
+        let content_extent = tree
+            .children(index)
+            .first()
+            .map(|&c| self.axis.main(tree.query_node_size(c)))
+            .unwrap_or(0.0);
+
+        let max_offset = (content_extent - viewport_extent).max(0.0);
+        ScrollMetrics {
+            content_extent,
+            viewport_extent,
+            offset: tree.get_signal(self.offset).clamp(0.0, max_offset),
+        }
+    }
+
+    fn scroll_by(&self, tree: &'static WidgetTree, index: NodeIndex, delta: f64) -> bool {
+        self.metrics(tree, index).scroll(tree, self.offset, delta)
+    }
+}
+
+impl Layouter for ScrollView {
+    fn on_scroll(&self, tree: &'static WidgetTree, index: NodeIndex, delta: Vec2) -> bool {
+        self.scroll_by(tree, index, self.axis.main(delta.to_size()))
+    }
+
+    fn constraints_for_child(
+        &self,
+        _tree: &'static WidgetTree,
+        _index: NodeIndex,
+        ctx: LayouterConstrainChildrenCtx,
+    ) -> Constraints {
+        ctx.self_constraints.unbounded(self.axis)
+    }
+
+    fn position_for_child(
+        &self,
+        tree: &'static WidgetTree,
+        index: NodeIndex,
+        _ctx: LayoutChildWasSizedCtx,
+    ) -> Point {
+        let metrics = self.metrics(tree, index);
+        self.axis.point(-metrics.offset, 0.0)
+    }
+
+    fn layer(&self, tree: &'static WidgetTree, index: NodeIndex) -> Option<Layer> {
+        Some(Layer::clip(Rect::from_origin_size(
+            Point::ORIGIN,
+            tree.query_node_size(index),
+        )))
+    }
+}
+
+/// How big each item of a `VirtualList` is along the list's axis
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum ItemExtent {
+    /// Every item is exactly this big
+    Fixed(f64),
+    /// Items are assumed to be this big until they've been laid out once, after which their
+    /// measured extent is remembered
+    Estimated(f64),
+}
+
+/// A materialized item of a `VirtualList`. The node stays the same as the list scrolls, and
+/// `item` is rebound to whichever item the node should currently show.
+struct VirtualListSlot {
+    node: NodeIndex,
+    item: Signal<usize>,
+}
+
+/// A scrolling list that only has nodes for the items in view (plus `overscan` items on either
+/// side). Item nodes are made by `builder`, which is given a signal holding the index of the
+/// item the node should show. Nodes are recycled as the list scrolls by rebinding that signal,
+/// so `builder` only runs when the list needs more nodes than it has made so far.
+struct VirtualList {
+    axis: Axis,
+    item_count: Signal<usize>,
+    extent: ItemExtent,
+    offset: Signal<f64>,
+    overscan: usize,
+    builder: Box<dyn Fn(&'static WidgetTree, Signal<usize>) -> NodeIndex>,
+
+    /// Materialized slots, in child order
+    slots: RefCell<Vec<VirtualListSlot>>,
+    /// Slots not currently attached to the list, ready to be reused
+    spare_slots: RefCell<Vec<VirtualListSlot>>,
+    /// Measured extents of items that have been laid out, for `ItemExtent::Estimated`
+    measured: RefCell<BTreeMap<usize, f64>>,
+    /// Bumped whenever `measured` changes, so that positions depending on it are invalidated
+    measured_revision: Signal<usize>,
+}
+
+impl VirtualList {
+    fn new(
+        tree: &'static WidgetTree,
+        axis: Axis,
+        item_count: Signal<usize>,
+        extent: ItemExtent,
+        offset: Signal<f64>,
+        builder: impl Fn(&'static WidgetTree, Signal<usize>) -> NodeIndex + 'static,
+    ) -> Self {
+        VirtualList {
+            axis,
+            item_count,
+            extent,
+            offset,
+            overscan: 2,
+            builder: Box::new(builder),
+            slots: RefCell::new(Vec::new()),
+            spare_slots: RefCell::new(Vec::new()),
+            measured: RefCell::new(BTreeMap::new()),
+            measured_revision: tree.create_signal(0),
+        }
+    }
+
+    fn item_extent(&self, item: usize) -> f64 {
+        match self.extent {
+            ItemExtent::Fixed(extent) => extent,
+            ItemExtent::Estimated(estimate) => {
+                self.measured.borrow().get(&item).copied().unwrap_or(estimate)
+            }
+        }
+    }
+
+    /// Offset of the start of `item` from the start of the list, ignoring scrolling
+    fn item_offset(&self, tree: &'static WidgetTree, item: usize) -> f64 {
+        match self.extent {
+            ItemExtent::Fixed(extent) => item as f64 * extent,
+            ItemExtent::Estimated(estimate) => {
+                tree.get_signal(self.measured_revision);
+                item as f64 * estimate
+                    + self
+                        .measured
+                        .borrow()
+                        .range(..item)
+                        .map(|(_, extent)| extent - estimate)
+                        .sum::<f64>()
+            }
+        }
+    }
+
+    /// The item under `offset`, clamped to the last item
+    fn item_at(&self, tree: &'static WidgetTree, offset: f64, item_count: usize) -> usize {
+        match self.extent {
+            ItemExtent::Fixed(extent) if extent > 0.0 => {
+                ((offset / extent).floor().max(0.0) as usize).min(item_count.saturating_sub(1))
+            }
+            _ => {
+                let (mut lo, mut hi) = (0, item_count.saturating_sub(1));
+                while lo < hi {
+                    let mid = (lo + hi + 1) / 2;
+                    if self.item_offset(tree, mid) <= offset {
+                        lo = mid;
+                    } else {
+                        hi = mid - 1;
+                    }
+                }
+                lo
+            }
+        }
+    }
+
+    fn metrics(&self, tree: &'static WidgetTree, index: NodeIndex) -> ScrollMetrics {
+        let item_count = tree.get_signal(self.item_count);
+        let content_extent = self.item_offset(tree, item_count);
+        let viewport_extent = self.axis.main(tree.query_node_size(index));
+
+        let max_offset = (content_extent - viewport_extent).max(0.0);
+        ScrollMetrics {
+            content_extent,
+            viewport_extent,
+            offset: tree.get_signal(self.offset).clamp(0.0, max_offset),
+        }
+    }
+
+    /// The item that slot `slot` should show when the window starts at `first`. Slots are used
+    /// as a ring buffer, so scrolling by one item only rebinds one slot.
+    fn item_for_slot(first: usize, slot: usize, slot_count: usize) -> usize {
+        first + (slot + slot_count - first % slot_count) % slot_count
+    }
+}
+
+impl Layouter for VirtualList {
+    fn on_scroll(&self, tree: &'static WidgetTree, index: NodeIndex, delta: Vec2) -> bool {
+        let delta = self.axis.main(delta.to_size());
+        self.metrics(tree, index).scroll(tree, self.offset, delta)
+    }
+
+    fn constraints_for_child(
+        &self,
+        tree: &'static WidgetTree,
+        index: NodeIndex,
+        _ctx: LayouterConstrainChildrenCtx,
+    ) -> Constraints {
+        let cross = self.axis.cross(tree.query_node_size(index));
+        match self.extent {
+            ItemExtent::Fixed(extent) => Constraints::tight(self.axis.size(extent, cross)),
+            ItemExtent::Estimated(_) => {
+                Constraints::tight(self.axis.size(0.0, cross)).unbounded(self.axis)
+            }
+        }
+    }
+
+    fn position_for_child(
+        &self,
+        tree: &'static WidgetTree,
+        index: NodeIndex,
+        ctx: LayoutChildWasSizedCtx,
+    ) -> Point {
+        // The slot is found by its node, so which child this is goes through the tree rather
+        // than trusting `slots` to be in step with it
+        let child = tree.query_nth_child(NthChild {
+            parent_index: index,
+            child_n: ctx.child_n,
+        });
+        let item = self
+            .slots
+            .borrow()
+            .iter()
+            .find(|slot| slot.node == child)
+            .map(|slot| slot.item)
+            .expect("every child of a list is one of its slots");
+        let item = tree.get_signal(item);
+        let offset = self.metrics(tree, index).offset;
+        self.axis.point(self.item_offset(tree, item) - offset, 0.0)
+    }
+
+    fn size_for_self(
+        &self,
+        tree: &'static WidgetTree,
+        _index: NodeIndex,
+        ctx: LayouterSizeSelfCtx,
+    ) -> Size {
+        let Constraints { min, max } = ctx.constraints;
+
+        let main = if self.axis.main(max).is_finite() {
+            self.axis.main(max)
+        } else {
+            self.item_offset(tree, tree.get_signal(self.item_count))
+        };
+        let cross = if self.axis.cross(max).is_finite() {
+            self.axis.cross(max)
+        } else {
+            self.axis.cross(min)
+        };
+
+        ctx.constraints.clamp_size(self.axis.size(main, cross))
+    }
+
+    fn layer(&self, tree: &'static WidgetTree, index: NodeIndex) -> Option<Layer> {
+        Some(Layer::clip(Rect::from_origin_size(
+            Point::ORIGIN,
+            tree.query_node_size(index),
+        )))
+    }
+
+    fn update(&self, tree: &'static WidgetTree, index: NodeIndex) {
+        let item_count = tree.get_signal(self.item_count);
+
+        // Remember the extents of whatever is currently laid out, before working out what's in
+        // view, so the window is computed from the best information we have
+        if let ItemExtent::Estimated(_) = self.extent {
+            // Items past the end are gone, and whatever is there if the list grows again may be
+            // a different size
+            let mut changed = !self.measured.borrow_mut().split_off(&item_count).is_empty();
+            for slot in self.slots.borrow().iter() {
+                let item = tree.get_signal(slot.item);
+                if item >= item_count {
+                    continue;
+                }
+
+                let extent = self.axis.main(tree.query_node_size(slot.node));
+                if self.item_extent(item) != extent {
+                    self.measured.borrow_mut().insert(item, extent);
+                    changed = true;
+                }
+            }
+
+            if changed {
+                let measured_revision = tree.get_signal(self.measured_revision);
+                tree.set_signal(self.measured_revision, measured_revision + 1);
+            }
+        }
+
+        let metrics = self.metrics(tree, index);
+        let (first, last) = if item_count == 0 {
+            (0, 0)
+        } else {
+            let first = self.item_at(tree, metrics.offset, item_count);
+            let last = self.item_at(
+                tree,
+                metrics.offset + metrics.viewport_extent,
+                item_count,
+            );
+            (
+                first.saturating_sub(self.overscan),
+                (last + self.overscan + 1).min(item_count),
+            )
+        };
+        let slot_count = last - first;
+
+        let mut slots = self.slots.borrow_mut();
+        if slots.len() != slot_count {
+            let mut spare_slots = self.spare_slots.borrow_mut();
+            while slots.len() > slot_count {
+                spare_slots.push(slots.pop().unwrap());
+            }
+            while slots.len() < slot_count {
+                let item = Self::item_for_slot(first, slots.len(), slot_count);
+                let slot = spare_slots.pop().unwrap_or_else(|| {
+                    let item = tree.create_signal(item);
+                    VirtualListSlot {
+                        node: (self.builder)(tree, item),
+                        item,
+                    }
+                });
+                slots.push(slot);
+            }
+
+            tree.set_children(index, &slots.iter().map(|s| s.node).collect::<Vec<_>>());
+        }
+
+        // Setting a signal invalidates positions, which read `slots`, so let go of it first
+        let bindings = slots
+            .iter()
+            .enumerate()
+            .map(|(n, slot)| (slot.item, Self::item_for_slot(first, n, slot_count)))
+            .collect::<Vec<_>>();
+        drop(slots);
+
+        for (signal, item) in bindings {
+            if tree.get_signal(signal) != item {
+                tree.set_signal(signal, item);
+            }
+        }
+    }
+
+    fn min_intrinsic_width(&self, tree: &'static WidgetTree, index: NodeIndex, height: f64) -> f64 {
+        match self.axis {
+            Axis::Horizontal => self.item_offset(tree, tree.get_signal(self.item_count)),
+            Axis::Vertical => tree
+                .children(index)
+                .into_iter()
+                .map(|c| tree.query_min_intrinsic_width(c, height))
+                .fold(0.0, f64::max),
+        }
+    }
+
+    fn max_intrinsic_width(&self, tree: &'static WidgetTree, index: NodeIndex, height: f64) -> f64 {
+        match self.axis {
+            Axis::Horizontal => self.item_offset(tree, tree.get_signal(self.item_count)),
+            Axis::Vertical => tree
+                .children(index)
+                .into_iter()
+                .map(|c| tree.query_max_intrinsic_width(c, height))
+                .fold(0.0, f64::max),
+        }
+    }
+
+    fn min_intrinsic_height(&self, tree: &'static WidgetTree, index: NodeIndex, width: f64) -> f64 {
+        match self.axis {
+            Axis::Horizontal => tree
+                .children(index)
+                .into_iter()
+                .map(|c| tree.query_min_intrinsic_height(c, width))
+                .fold(0.0, f64::max),
+            Axis::Vertical => self.item_offset(tree, tree.get_signal(self.item_count)),
+        }
+    }
+
+    fn max_intrinsic_height(&self, tree: &'static WidgetTree, index: NodeIndex, width: f64) -> f64 {
+        match self.axis {
+            Axis::Horizontal => tree
+                .children(index)
+                .into_iter()
+                .map(|c| tree.query_max_intrinsic_height(c, width))
+                .fold(0.0, f64::max),
+            Axis::Vertical => self.item_offset(tree, tree.get_signal(self.item_count)),
+        }
+    }
+}
+
+/// Applies an affine transform to the drawing of its subtree, around the point given by
+/// `origin`. Layout is unaffected: the node takes up the same space as its untransformed child.
+struct Transform {
+    transform: MaybeSignal<Affine>,
+    origin: Alignment,
+}
+
+impl Transform {
+    fn new(transform: Affine) -> Self {
+        Transform {
+            transform: MaybeSignal::Value(transform),
+            origin: Alignment::CENTER,
+        }
+    }
+
+    fn from_signal(transform: Signal<Affine>) -> Self {
+        Transform {
+            transform: MaybeSignal::Signal(transform),
+            origin: Alignment::CENTER,
+        }
+    }
+}
+
+impl Layouter for Transform {
+    fn transform(&self, tree: &'static WidgetTree, index: NodeIndex) -> Affine {
+        let origin = self
+            .origin
+            .resolve(tree.text_direction())
+            .position(tree.query_node_size(index), Size::ZERO)
+            .to_vec2();
+        Affine::translate(origin) * self.transform.get(tree) * Affine::translate(-origin)
+    }
+}
+
+/// Sizes its child to the child's max intrinsic width, e.g. so that a column of buttons can all
+/// be as wide as the widest one rather than as wide as the column.
+struct IntrinsicWidth {}
+
+impl Layouter for IntrinsicWidth {
+    fn constraints_for_child(
+        &self,
+        tree: &'static WidgetTree,
+        index: NodeIndex,
+        ctx: LayouterConstrainChildrenCtx,
+    ) -> Constraints {
+        let child = tree.query_nth_child(NthChild {
+            parent_index: index,
+            child_n: ctx.child_n,
+        });
+        let width = tree.query_max_intrinsic_width(child, ctx.self_constraints.max.height);
+
+        ctx.self_constraints.tighten(Some(width), None)
+    }
+
+    fn size_for_self(
+        &self,
+        tree: &'static WidgetTree,
+        index: NodeIndex,
+        ctx: LayouterSizeSelfCtx,
+    ) -> Size {
+        match tree.children(index).first() {
+            Some(&c) => ctx.constraints.clamp_size(tree.query_node_size(c)),
+            None => ctx.constraints.min,
+        }
+    }
+}
+
+/// Sizes its child to the child's max intrinsic height, e.g. so that the children of a row can
+/// all be as tall as the tallest one.
+struct IntrinsicHeight {}
+
+impl Layouter for IntrinsicHeight {
+    fn constraints_for_child(
+        &self,
+        tree: &'static WidgetTree,
+        index: NodeIndex,
+        ctx: LayouterConstrainChildrenCtx,
+    ) -> Constraints {
+        let child = tree.query_nth_child(NthChild {
+            parent_index: index,
+            child_n: ctx.child_n,
+        });
+        let height = tree.query_max_intrinsic_height(child, ctx.self_constraints.max.width);
+
+        ctx.self_constraints.tighten(None, Some(height))
+    }
+
+    fn size_for_self(
+        &self,
+        tree: &'static WidgetTree,
+        index: NodeIndex,
+        ctx: LayouterSizeSelfCtx,
+    ) -> Size {
+        match tree.children(index).first() {
+            Some(&c) => ctx.constraints.clamp_size(tree.query_node_size(c)),
+            None => ctx.constraints.min,
+        }
+    }
+}
+
+/// The variables a `ConstraintLayout` solves for one box: its top-left corner and its size, in
+/// the layout's coordinate space
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+struct ConstraintAnchors {
+    left: Variable,
+    top: Variable,
+    width: Variable,
+    height: Variable,
+}
+
+impl ConstraintAnchors {
+    fn new() -> Self {
+        ConstraintAnchors {
+            left: Variable::new(),
+            top: Variable::new(),
+            width: Variable::new(),
+            height: Variable::new(),
+        }
+    }
+
+    fn right(&self) -> Expression {
+        self.left + self.width
+    }
+
+    fn bottom(&self) -> Expression {
+        self.top + self.height
+    }
+
+    fn center_x(&self) -> Expression {
+        self.left + self.width * 0.5
+    }
+
+    fn center_y(&self) -> Expression {
+        self.top + self.height * 0.5
+    }
+}
+
+/// Wraps a child of a `ConstraintLayout` to say which anchors it's placed and sized by
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct ConstraintItem {
+    anchors: ConstraintAnchors,
+}
+
+impl Layouter for ConstraintItem {
+    fn parent_data(&self) -> Option<&dyn Any> {
+        Some(self)
+    }
+}
+
+/// Places and sizes its `ConstraintItem` children by solving a set of linear constraints between
+/// their anchors, e.g. `label.right() + 8.0 |EQ(REQUIRED)| field.left`. Constraints that can't
+/// all be met are broken weakest first, and a required constraint that contradicts the others is
+/// refused by `add_constraint`.
+///
+/// The layout's own box is `parent`, which wants to fill the available space. Each item would
+/// like to be its intrinsic size, but only weakly, so any other constraint wins over that. The
+/// solver is kept between layouts, and only the inputs that actually changed (available space,
+/// intrinsic sizes, bound signals) are fed back in, so re-solving is incremental.
+///
+/// An item that stops being a child is taken out of the solver, along with every constraint that
+/// mentions it, until it's a child again.
+///
+/// Children that aren't wrapped in a `ConstraintItem` are placed at the origin.
+struct ConstraintLayout {
+    parent: ConstraintAnchors,
+    solver: RefCell<Solver>,
+    bindings: Vec<(Variable, Signal<f64>)>,
+    /// Constraints added with `add_constraint`, and whether each is in the solver
+    constraints: RefCell<Vec<(Constraint, bool)>>,
+
+    /// Items that are children, with the constraints that keep their sizes from going negative
+    items: RefCell<HashMap<ConstraintAnchors, Vec<Constraint>>>,
+    /// Items that were children but aren't any more
+    removed_items: RefCell<HashSet<ConstraintAnchors>>,
+    /// When the children last changed, as of the last time `items` was brought in line with them
+    items_revision: RefCell<Option<usize>>,
+    /// The last value suggested for each edit variable
+    suggested: RefCell<HashMap<Variable, f64>>,
+    solution: RefCell<HashMap<Variable, f64>>,
+}
+
+impl ConstraintLayout {
+    fn new() -> Self {
+        let parent = ConstraintAnchors::new();
+
+        let mut solver = Solver::new();
+        solver
+            .add_constraints(&[
+                parent.left | EQ(REQUIRED) | 0.0,
+                parent.top | EQ(REQUIRED) | 0.0,
+                parent.width | GE(REQUIRED) | 0.0,
+                parent.height | GE(REQUIRED) | 0.0,
+            ])
+            .unwrap();
+        solver.add_edit_variable(parent.width, STRONG).unwrap();
+        solver.add_edit_variable(parent.height, STRONG).unwrap();
+
+        ConstraintLayout {
+            parent,
+            solver: RefCell::new(solver),
+            bindings: Vec::new(),
+            constraints: RefCell::new(Vec::new()),
+            items: RefCell::new(HashMap::new()),
+            removed_items: RefCell::new(HashSet::new()),
+            items_revision: RefCell::new(None),
+            suggested: RefCell::new(HashMap::new()),
+            solution: RefCell::new(HashMap::new()),
+        }
+    }
+
+    fn add_constraint(&mut self, constraint: Constraint) -> Result<()> {
+        self.solver
+            .get_mut()
+            .add_constraint(constraint.clone())
+            .map_err(|e| anyhow::anyhow!("failed to add constraint: {:?}", e))?;
+        self.constraints.get_mut().push((constraint, true));
+        Ok(())
+    }
+
+    /// A variable that strongly follows `signal`, for use in constraints whose values change at
+    /// runtime, e.g. `field.width |GE(REQUIRED)| min_width`
+    fn bind(&mut self, signal: Signal<f64>) -> Result<Variable> {
+        let variable = Variable::new();
+        self.solver
+            .get_mut()
+            .add_edit_variable(variable, STRONG)
+            .map_err(|e| anyhow::anyhow!("failed to bind signal: {:?}", e))?;
+        self.bindings.push((variable, signal));
+        Ok(variable)
+    }
+
+    fn value(&self, variable: Variable) -> f64 {
+        self.solution
+            .borrow()
+            .get(&variable)
+            .copied()
+            .unwrap_or(0.0)
+    }
+
+    fn suggest(&self, solver: &mut Solver, variable: Variable, value: f64) {
+        if !value.is_finite() {
+            return;
+        }
+
+        // Only variables that were never registered as edit variables can be refused
+        let mut suggested = self.suggested.borrow_mut();
+        if suggested.get(&variable) != Some(&value) && solver.suggest_value(variable, value).is_ok()
+        {
+            suggested.insert(variable, value);
+        }
+    }
+
+    /// Lets an item's size follow its intrinsic size, returning what has to be removed again
+    /// when it stops being a child. Nothing added here is required, so it can never make the
+    /// system unsatisfiable; only `add_constraint` can do that, and it says so.
+    fn register_item(solver: &mut Solver, anchors: ConstraintAnchors) -> Vec<Constraint> {
+        [anchors.width, anchors.height]
+            .into_iter()
+            .map(|variable| {
+                // Non-required constraints are only refused if they're already there
+                let constraint: Constraint = variable | GE(STRONG) | 0.0;
+                let _ = solver.add_constraint(constraint.clone());
+                if !solver.has_edit_variable(&variable) {
+                    let _ = solver.add_edit_variable(variable, WEAK);
+                }
+                constraint
+            })
+            .collect()
+    }
+
+    /// Brings the items registered with the solver in line with the children, if they've
+    /// changed since last time. Constraints that mention an item that isn't a child any more are
+    /// taken out, and put back once it is. One that no longer fits with the rest when it's put
+    /// back stays out until the children change again.
+    fn sync_items(&self, tree: &'static WidgetTree, index: NodeIndex, solver: &mut Solver) {
+        let revision = tree.children_revision(index);
+        if self.items_revision.replace(Some(revision)) == Some(revision) {
+            return;
+        }
+
+        let children = tree
+            .children(index)
+            .into_iter()
+            .filter_map(|c| tree.parent_data::<ConstraintItem>(c))
+            .map(|item| item.anchors)
+            .collect::<HashSet<_>>();
+
+        let mut items = self.items.borrow_mut();
+        let mut removed_items = self.removed_items.borrow_mut();
+        let mut suggested = self.suggested.borrow_mut();
+        items.retain(|anchors, size_constraints| {
+            if children.contains(anchors) {
+                return true;
+            }
+            for constraint in size_constraints.iter() {
+                let _ = solver.remove_constraint(constraint);
+            }
+            for variable in [anchors.width, anchors.height] {
+                let _ = solver.remove_edit_variable(variable);
+                suggested.remove(&variable);
+            }
+            removed_items.insert(*anchors);
+            false
+        });
+        for &anchors in &children {
+            removed_items.remove(&anchors);
+            items
+                .entry(anchors)
+                .or_insert_with(|| Self::register_item(solver, anchors));
+        }
+
+        let detached = removed_items
+            .iter()
+            .flat_map(|anchors| [anchors.left, anchors.top, anchors.width, anchors.height])
+            .collect::<HashSet<_>>();
+        for (constraint, active) in self.constraints.borrow_mut().iter_mut() {
+            let attached = constraint
+                .expr()
+                .terms
+                .iter()
+                .all(|term| !detached.contains(&term.variable));
+            if attached && !*active {
+                *active = solver.add_constraint(constraint.clone()).is_ok();
+            } else if !attached && *active {
+                let _ = solver.remove_constraint(constraint);
+                *active = false;
+            }
+        }
+    }
+
+    /// Brings the solution up to date. All of the inputs are read on every call, even when
+    /// nothing changed, so whichever query is asking picks up the right dependencies.
+    fn solve(&self, tree: &'static WidgetTree, index: NodeIndex) {
+        let constraints = tree.query_node_constraints(index);
+        let available = Size::new(
+            if constraints.has_bounded_width() {
+                constraints.max.width
+            } else {
+                constraints.min.width
+            },
+            if constraints.has_bounded_height() {
+                constraints.max.height
+            } else {
+                constraints.min.height
+            },
+        );
+
+        let bound_values = self
+            .bindings
+            .iter()
+            .map(|&(variable, signal)| (variable, tree.get_signal(signal)))
+            .collect::<Vec<_>>();
+
+        let preferred_sizes = tree
+            .children(index)
+            .into_iter()
+            .filter_map(|c| {
+                let item = tree.parent_data::<ConstraintItem>(c)?;
+                let width = tree.query_max_intrinsic_width(c, f64::INFINITY);
+                let height = tree.query_max_intrinsic_height(c, width);
+                Some((item.anchors, Size::new(width, height)))
+            })
+            .collect::<Vec<_>>();
+
+        let mut solver = self.solver.borrow_mut();
+        self.sync_items(tree, index, &mut solver);
+
+        self.suggest(&mut solver, self.parent.width, available.width);
+        self.suggest(&mut solver, self.parent.height, available.height);
+
+        for (variable, value) in bound_values {
+            self.suggest(&mut solver, variable, value);
+        }
+
+        for (anchors, size) in preferred_sizes {
+            self.suggest(&mut solver, anchors.width, size.width);
+            self.suggest(&mut solver, anchors.height, size.height);
+        }
+
+        let mut solution = self.solution.borrow_mut();
+        for &(variable, value) in solver.fetch_changes() {
+            solution.insert(variable, value);
+        }
+    }
+
+    fn item_anchors(
+        &self,
+        tree: &'static WidgetTree,
+        index: NodeIndex,
+        child_n: usize,
+    ) -> Option<ConstraintAnchors> {
+        let child = tree.query_nth_child(NthChild {
+            parent_index: index,
+            child_n,
+        });
+        tree.parent_data::<ConstraintItem>(child)
+            .map(|item| item.anchors)
+    }
+}
+
+impl Layouter for ConstraintLayout {
+    fn constraints_for_child(
+        &self,
+        tree: &'static WidgetTree,
+        index: NodeIndex,
+        ctx: LayouterConstrainChildrenCtx,
+    ) -> Constraints {
+        let Some(anchors) = self.item_anchors(tree, index, ctx.child_n) else {
+            return ctx.self_constraints.loosen();
+        };
+
+        self.solve(tree, index);
+        Constraints::tight(Size::new(
+            self.value(anchors.width).max(0.0),
+            self.value(anchors.height).max(0.0),
+        ))
+    }
+
+    fn position_for_child(
+        &self,
+        tree: &'static WidgetTree,
+        index: NodeIndex,
+        ctx: LayoutChildWasSizedCtx,
+    ) -> Point {
+        let Some(anchors) = self.item_anchors(tree, index, ctx.child_n) else {
+            return Point::ORIGIN;
+        };
+
+        self.solve(tree, index);
+        Point::new(self.value(anchors.left), self.value(anchors.top))
+    }
+
+    fn size_for_self(
+        &self,
+        tree: &'static WidgetTree,
+        index: NodeIndex,
+        ctx: LayouterSizeSelfCtx,
+    ) -> Size {
+        self.solve(tree, index);
+        ctx.constraints.clamp_size(Size::new(
+            self.value(self.parent.width),
+            self.value(self.parent.height),
+        ))
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum TableColumn {
+    /// Exactly this many pixels
+    Fixed(f64),
+    /// A share of the width left over once every other column is sized. Sized like `Content`
+    /// when the table is unbounded horizontally.
+    Flex(f64),
+    /// As wide as the widest cell that sits only in this column
+    Content,
+}
+
+/// Where a child of a `Table` goes, and how it sits in its cell. Without an alignment, cells
+/// use the table's `header_alignment` or `cell_alignment`, depending on their row.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct TableCell {
+    row: usize,
+    column: usize,
+    row_span: usize,
+    column_span: usize,
+    alignment: Option<Alignment>,
+}
+
+impl TableCell {
+    fn at(row: usize, column: usize) -> Self {
+        TableCell {
+            row,
+            column,
+            row_span: 1,
+            column_span: 1,
+            alignment: None,
+        }
+    }
+
+    fn spanning(self, row_span: usize, column_span: usize) -> Self {
+        TableCell {
+            row_span: row_span.max(1),
+            column_span: column_span.max(1),
+            ..self
+        }
+    }
+
+    fn aligned(self, alignment: Alignment) -> Self {
+        TableCell {
+            alignment: Some(alignment),
+            ..self
+        }
+    }
+
+    fn span(&self, axis: Axis) -> std::ops::Range<usize> {
+        match axis {
+            Axis::Horizontal => self.column..self.column + self.column_span,
+            Axis::Vertical => self.row..self.row + self.row_span,
+        }
+    }
+}
+
+impl Layouter for TableCell {
+    fn parent_data(&self) -> Option<&dyn Any> {
+        Some(self)
+    }
+}
+
+/// Lays out `TableCell`s in rows, with every column the same width all the way down. The first
+/// `header_rows` rows are headers, whose cells are aligned with `header_alignment`.
+///
+/// Cells are as wide as their column (or columns) allow and as tall as they like, and each row is
+/// as tall as its tallest cell. `Content` columns are sized from the max intrinsic widths of
+/// their cells, measured one column at a time, so changing a cell only re-measures its column.
+struct Table {
+    columns: Vec<TableColumn>,
+    header_rows: usize,
+    column_gap: f64,
+    row_gap: f64,
+    header_alignment: Alignment,
+    cell_alignment: Alignment,
+}
+
+impl Table {
+    fn new(columns: Vec<TableColumn>) -> Self {
+        Table {
+            columns,
+            header_rows: 0,
+            column_gap: 0.0,
+            row_gap: 0.0,
+            header_alignment: Alignment::CENTER,
+            cell_alignment: Alignment::CENTER_START,
+        }
+    }
+
+    fn header_rows(self, header_rows: usize) -> Self {
+        Table {
+            header_rows,
+            ..self
+        }
+    }
+
+    /// Adds the table to `tree` with `rows[r][c]` in row `r` and column `c`, and returns its node.
+    /// Cells that span rows or columns need adding with their own `TableCell` instead.
+    fn build(self, tree: &'static WidgetTree, rows: &[Vec<NodeIndex>]) -> NodeIndex {
+        let table = tree.add_node(Box::new(self), None);
+
+        let cells = rows
+            .iter()
+            .enumerate()
+            .flat_map(|(row, cells)| {
+                cells.iter().enumerate().map(move |(column, &cell)| {
+                    tree.add_child_return_parent(TableCell::at(row, column), cell)
+                })
+            })
+            .collect::<Vec<_>>();
+        tree.set_children(table, &cells);
+
+        table
+    }
+
+    /// A table without any columns has a single `Content` one
+    fn columns(&self) -> Vec<TableColumn> {
+        if self.columns.is_empty() {
+            return vec![TableColumn::Content];
+        }
+        self.columns.clone()
+    }
+
+    /// The cell of every child, in child order. Children that aren't `TableCell`s go in the
+    /// first column of row 0.
+    fn cells(&self, tree: &'static WidgetTree, index: NodeIndex) -> Vec<TableCell> {
+        let column_count = self.columns().len();
+        tree.children(index)
+            .into_iter()
+            .map(|c| {
+                let cell = tree
+                    .parent_data::<TableCell>(c)
+                    .unwrap_or(TableCell::at(0, 0));
+                let column = cell.column.min(column_count - 1);
+                TableCell {
+                    column,
+                    column_span: cell.column_span.min(column_count - column),
+                    ..cell
+                }
+            })
+            .collect()
+    }
+
+    fn alignment(&self, cell: &TableCell) -> Alignment {
+        cell.alignment.unwrap_or(if cell.row < self.header_rows {
+            self.header_alignment
+        } else {
+            self.cell_alignment
+        })
+    }
+
+    fn is_content_sized(&self, column: TableColumn, available: f64) -> bool {
+        match column {
+            TableColumn::Fixed(_) => false,
+            TableColumn::Flex(_) => !available.is_finite(),
+            TableColumn::Content => true,
+        }
+    }
+
+    fn column_widths(
+        &self,
+        tree: &'static WidgetTree,
+        index: NodeIndex,
+        available: f64,
+    ) -> Vec<f64> {
+        let columns = self.columns();
+        let mut widths = columns
+            .iter()
+            .enumerate()
+            .map(|(column, &track)| match track {
+                TableColumn::Fixed(px) => px,
+                _ if self.is_content_sized(track, available) => {
+                    tree.query_layout_measure(LayoutMeasure { index, key: column })
+                }
+                _ => 0.0,
+            })
+            .collect::<Vec<_>>();
+
+        // Cells spanning several columns share whatever they don't already fit in between the
+        // content-sized columns they span
+        for (child, cell) in tree
+            .children(index)
+            .into_iter()
+            .zip(self.cells(tree, index))
+        {
+            let span = cell.span(Axis::Horizontal);
+            let content_sized = span
+                .clone()
+                .filter(|&c| self.is_content_sized(columns[c], available))
+                .collect::<Vec<_>>();
+            if span.len() == 1 || content_sized.is_empty() {
+                continue;
+            }
+
+            let width = tree.query_max_intrinsic_width(child, f64::INFINITY);
+            let excess = width - self.span_extent(&widths, Axis::Horizontal, span);
+            if excess > 0.0 {
+                for c in &content_sized {
+                    widths[*c] += excess / content_sized.len() as f64;
+                }
+            }
+        }
+
+        // todo(chad): shrink content-sized columns towards their min intrinsic widths when the
+        // table doesn't fit, rather than overflowing
+        if available.is_finite() {
+            let total_flex = columns
+                .iter()
+                .map(|c| match c {
+                    TableColumn::Flex(f) => *f,
+                    _ => 0.0,
+                })
+                .sum::<f64>();
+
+            if total_flex > 0.0 {
+                let used = self.span_extent(&widths, Axis::Horizontal, 0..widths.len());
+                let leftover = (available - used).max(0.0);
+
+                for (width, column) in widths.iter_mut().zip(&columns) {
+                    if let TableColumn::Flex(f) = column {
+                        *width = leftover * f / total_flex;
+                    }
+                }
+            }
+        }
+
+        widths
+    }
+
+    fn row_heights(&self, tree: &'static WidgetTree, index: NodeIndex) -> Vec<f64> {
+        let cells = self.cells(tree, index);
+        let row_count = cells.iter().map(|c| c.row + c.row_span).max().unwrap_or(0);
+        let mut heights = vec![0.0; row_count];
+
+        let children = tree.children(index);
+        for (&child, cell) in children.iter().zip(&cells) {
+            if cell.row_span == 1 {
+                heights[cell.row] = f64::max(heights[cell.row], tree.query_node_size(child).height);
+            }
+        }
+
+        // Cells spanning several rows push their last row down if they don't fit
+        for (&child, cell) in children.iter().zip(&cells) {
+            let span = cell.span(Axis::Vertical);
+            if span.len() == 1 {
+                continue;
+            }
+
+            let height = tree.query_node_size(child).height;
+            let excess = height - self.span_extent(&heights, Axis::Vertical, span.clone());
+            if excess > 0.0 {
+                heights[span.end - 1] += excess;
+            }
+        }
+
+        heights
+    }
+
+    fn intrinsic_width(&self, tree: &'static WidgetTree, index: NodeIndex) -> f64 {
+        let widths = self.column_widths(tree, index, f64::INFINITY);
+        self.span_extent(&widths, Axis::Horizontal, 0..widths.len())
+    }
+}
+
+impl TrackLayout for Table {
+    fn gap(&self, axis: Axis) -> f64 {
+        match axis {
+            Axis::Horizontal => self.column_gap,
+            Axis::Vertical => self.row_gap,
+        }
+    }
+}
+
+impl Layouter for Table {
+    fn constraints_for_child(
+        &self,
+        tree: &'static WidgetTree,
+        index: NodeIndex,
+        ctx: LayouterConstrainChildrenCtx,
+    ) -> Constraints {
+        let cell = self.cells(tree, index)[ctx.child_n];
+        let widths = self.column_widths(tree, index, ctx.self_constraints.max.width);
+        let width = self.span_extent(&widths, Axis::Horizontal, cell.span(Axis::Horizontal));
+
+        Constraints::loose(Size::new(width, f64::INFINITY))
+    }
+
+    fn position_for_child(
+        &self,
+        tree: &'static WidgetTree,
+        index: NodeIndex,
+        ctx: LayoutChildWasSizedCtx,
+    ) -> Point {
+        let cell = self.cells(tree, index)[ctx.child_n];
+        let widths = self.column_widths(tree, index, tree.query_node_constraints(index).max.width);
+        let heights = self.row_heights(tree, index);
+
+        let cell_rect = Rect::from_origin_size(
+            Point::new(
+                self.track_offset(&widths, Axis::Horizontal, cell.column),
+                self.track_offset(&heights, Axis::Vertical, cell.row),
+            ),
+            Size::new(
+                self.span_extent(&widths, Axis::Horizontal, cell.span(Axis::Horizontal)),
+                self.span_extent(&heights, Axis::Vertical, cell.span(Axis::Vertical)),
+            ),
+        );
+
+        // Right-to-left tables number their columns from the right
+        let cell_rect = match tree.text_direction() {
+            TextDirection::Ltr => cell_rect,
+            TextDirection::Rtl => {
+                let width = tree.query_node_size(index).width;
+                cell_rect.with_origin((width - cell_rect.x1, cell_rect.y0))
+            }
+        };
+
+        let child = tree.query_nth_child(NthChild {
+            parent_index: index,
+            child_n: ctx.child_n,
+        });
+        cell_rect.origin()
+            + self
+                .alignment(&cell)
+                .resolve(tree.text_direction())
+                .position(cell_rect.size(), tree.query_node_size(child))
+                .to_vec2()
+    }
+
+    fn size_for_self(
+        &self,
+        tree: &'static WidgetTree,
+        index: NodeIndex,
+        ctx: LayouterSizeSelfCtx,
+    ) -> Size {
+        let widths = self.column_widths(tree, index, ctx.constraints.max.width);
+        let heights = self.row_heights(tree, index);
+
+        ctx.constraints.clamp_size(Size::new(
+            self.span_extent(&widths, Axis::Horizontal, 0..widths.len()),
+            self.span_extent(&heights, Axis::Vertical, 0..heights.len()),
+        ))
+    }
+
+    fn measure(&self, tree: &'static WidgetTree, index: NodeIndex, column: usize) -> f64 {
+        tree.children(index)
+            .into_iter()
+            .zip(self.cells(tree, index))
+            .filter(|(_, cell)| cell.column == column && cell.column_span == 1)
+            .map(|(child, _)| tree.query_max_intrinsic_width(child, f64::INFINITY))
+            .fold(0.0, f64::max)
+    }
+
+    fn min_intrinsic_width(
+        &self,
+        tree: &'static WidgetTree,
+        index: NodeIndex,
+        _height: f64,
+    ) -> f64 {
+        self.intrinsic_width(tree, index)
+    }
+
+    fn max_intrinsic_width(
+        &self,
+        tree: &'static WidgetTree,
+        index: NodeIndex,
+        _height: f64,
+    ) -> f64 {
+        self.intrinsic_width(tree, index)
+    }
+}
+
+/// One pane of a `Split`. Panes share the space left over after dividers in proportion to their
+/// `fraction`s, but never go below `min` or above `max`. Dragging a collapsible pane's divider
+/// past half of its `min` collapses it to nothing, and dragging it back out restores it.
+#[derive(Clone, Copy, Debug)]
+struct SplitPane {
+    fraction: Signal<f64>,
+    collapsed: Signal<bool>,
+    min: f64,
+    max: f64,
+    collapsible: bool,
+}
+
+impl SplitPane {
+    fn new(tree: &'static WidgetTree, fraction: f64) -> Self {
+        SplitPane {
+            fraction: tree.create_signal(fraction),
+            collapsed: tree.create_signal(false),
+            min: 0.0,
+            max: f64::INFINITY,
+            collapsible: false,
+        }
+    }
+
+    fn min(self, min: f64) -> Self {
+        SplitPane { min, ..self }
+    }
+
+    fn max(self, max: f64) -> Self {
+        SplitPane { max, ..self }
+    }
+
+    fn collapsible(self) -> Self {
+        SplitPane {
+            collapsible: true,
+            ..self
+        }
+    }
+
+    fn clamp(&self, extent: f64) -> f64 {
+        extent.max(self.min).min(self.max)
+    }
+}
+
+/// Divides its space along `axis` between panes, with a draggable divider between each pair.
+/// Its children alternate between panes and dividers, so build it with `Split::build` rather than
+/// adding children directly.
+struct Split {
+    axis: Axis,
+    panes: Vec<SplitPane>,
+    divider_thickness: f64,
+    divider_color: Color,
+
+    /// The divider being dragged, and how far into it the pointer grabbed it
+    dragging: RefCell<Option<(usize, f64)>>,
+}
+
+impl Split {
+    fn new(axis: Axis, panes: Vec<SplitPane>) -> Self {
+        Split {
+            axis,
+            panes,
+            divider_thickness: 6.0,
+            divider_color: palette::css::DARK_GRAY,
+            dragging: RefCell::new(None),
+        }
+    }
+
+    /// Adds the split to `tree` with `children` as its panes, in order, and returns its node.
+    /// Fails if there isn't exactly one child per pane.
+    fn build(self, tree: &'static WidgetTree, children: &[NodeIndex]) -> Result<NodeIndex> {
+        if children.len() != self.panes.len() {
+            anyhow::bail!(
+                "a split with {} panes needs as many children, not {}",
+                self.panes.len(),
+                children.len()
+            );
+        }
+
+        let divider_color = self.divider_color;
+        let split = tree.add_node(Box::new(self), None);
+
+        let mut split_children = Vec::new();
+        for (n, &child) in children.iter().enumerate() {
+            if n > 0 {
+                split_children.push(tree.add_node(
+                    Box::new(SplitDivider {}),
+                    Some(Box::new(SplitDividerDrawer {
+                        color: divider_color,
+                    })),
+                ));
+            }
+            split_children.push(child);
+        }
+        tree.set_children(split, &split_children);
+
+        Ok(split)
+    }
+
+    fn available_extent(&self, tree: &'static WidgetTree, index: NodeIndex) -> f64 {
+        let dividers = self.divider_thickness * self.panes.len().saturating_sub(1) as f64;
+        (self.axis.main(tree.query_node_size(index)) - dividers).max(0.0)
+    }
+
+    /// How much of `available` each pane gets. Space is shared out by fraction, and panes that
+    /// hit their limits are frozen there while the rest is shared out again between the others.
+    fn pane_extents(&self, tree: &'static WidgetTree, available: f64) -> Vec<f64> {
+        let mut extents = vec![0.0; self.panes.len()];
+        let mut open = (0..self.panes.len())
+            .filter(|&i| !tree.get_signal(self.panes[i].collapsed))
+            .collect::<Vec<_>>();
+        let mut remaining = available;
+
+        while !open.is_empty() {
+            let fractions = open
+                .iter()
+                .map(|&i| tree.get_signal(self.panes[i].fraction).max(0.0))
+                .collect::<Vec<_>>();
+            let total_fraction = fractions.iter().sum::<f64>();
+
+            let mut total_violation = 0.0;
+            for (&i, fraction) in open.iter().zip(&fractions) {
+                let share = if total_fraction > 0.0 {
+                    remaining * fraction / total_fraction
+                } else {
+                    remaining / open.len() as f64
+                };
+                extents[i] = self.panes[i].clamp(share);
+                total_violation += extents[i] - share;
+            }
+
+            // Growing panes up to their minimum takes space from the others, so freeze those
+            // first. Shrinking panes down to their maximum frees space up, so freeze those first.
+            let frozen = open
+                .iter()
+                .copied()
+                .filter(|&i| {
+                    let pane = &self.panes[i];
+                    if total_violation > 0.0 {
+                        extents[i] == pane.min && pane.min > 0.0
+                    } else if total_violation < 0.0 {
+                        extents[i] == pane.max
+                    } else {
+                        true
+                    }
+                })
+                .collect::<Vec<_>>();
+
+            if frozen.is_empty() || frozen.len() == open.len() || total_violation == 0.0 {
+                break;
+            }
+
+            // Minimums can add up to more than there is, in which case the rest get nothing
+            for i in frozen {
+                remaining = (remaining - extents[i]).max(0.0);
+                open.retain(|&o| o != i);
+            }
+        }
+
+        extents
+    }
+
+    /// Where each pane starts along the axis
+    fn pane_offsets(&self, extents: &[f64]) -> Vec<f64> {
+        let mut offset = 0.0;
+        extents
+            .iter()
+            .map(|extent| {
+                let start = offset;
+                offset += extent + self.divider_thickness;
+                start
+            })
+            .collect()
+    }
+
+    /// Moves divider `divider` so that it starts at `start`, resizing the panes either side of it
+    fn drag_divider(
+        &self,
+        tree: &'static WidgetTree,
+        index: NodeIndex,
+        divider: usize,
+        start: f64,
+    ) {
+        let extents = self.pane_extents(tree, self.available_extent(tree, index));
+        let offsets = self.pane_offsets(&extents);
+
+        let (before, after) = (&self.panes[divider], &self.panes[divider + 1]);
+        let combined = extents[divider] + extents[divider + 1];
+        let requested = start - offsets[divider];
+
+        // Setting a signal invalidates everything that read it, so only set the ones that change
+        let collapse_before = before.collapsible && requested < before.min / 2.0;
+        let collapse_after = after.collapsible && combined - requested < after.min / 2.0;
+        if tree.get_signal(before.collapsed) != collapse_before {
+            tree.set_signal(before.collapsed, collapse_before);
+        }
+        if tree.get_signal(after.collapsed) != (collapse_after && !collapse_before) {
+            tree.set_signal(after.collapsed, collapse_after && !collapse_before);
+        }
+        if collapse_before || collapse_after {
+            return;
+        }
+
+        let lowest = before.min.max(combined - after.max);
+        let highest = before.max.min(combined - after.min);
+        let extent_before = requested.max(lowest).min(highest);
+
+        // Only the two panes either side of the divider change, so only their share of the
+        // fractions is redistributed
+        let total_fraction = tree.get_signal(before.fraction) + tree.get_signal(after.fraction);
+        if combined > 0.0 {
+            let fraction_before = total_fraction * extent_before / combined;
+            if fraction_before != tree.get_signal(before.fraction) {
+                tree.set_signal(before.fraction, fraction_before);
+                tree.set_signal(after.fraction, total_fraction - fraction_before);
+            }
+        }
+    }
+
+    /// Right-to-left horizontal splits put the first pane on the right
+    fn is_mirrored(&self, tree: &'static WidgetTree) -> bool {
+        self.axis == Axis::Horizontal && tree.text_direction() == TextDirection::Rtl
+    }
+
+    fn extents_for_child(
+        &self,
+        tree: &'static WidgetTree,
+        index: NodeIndex,
+        child_n: usize,
+    ) -> (f64, f64) {
+        let extents = self.pane_extents(tree, self.available_extent(tree, index));
+        let offsets = self.pane_offsets(&extents);
+
+        // Children alternate between panes and the dividers after them
+        let pane = child_n / 2;
+        if child_n % 2 == 0 {
+            (offsets[pane], extents[pane])
+        } else {
+            (offsets[pane] + extents[pane], self.divider_thickness)
+        }
+    }
+}
+
+impl Layouter for Split {
+    fn constraints_for_child(
+        &self,
+        tree: &'static WidgetTree,
+        index: NodeIndex,
+        ctx: LayouterConstrainChildrenCtx,
+    ) -> Constraints {
+        let (_, main) = self.extents_for_child(tree, index, ctx.child_n);
+        let cross = self.axis.cross(tree.query_node_size(index));
+        Constraints::tight(self.axis.size(main, cross))
+    }
+
+    fn position_for_child(
+        &self,
+        tree: &'static WidgetTree,
+        index: NodeIndex,
+        ctx: LayoutChildWasSizedCtx,
+    ) -> Point {
+        let (offset, extent) = self.extents_for_child(tree, index, ctx.child_n);
+        if self.is_mirrored(tree) {
+            let width = tree.query_node_size(index).width;
+            return Point::new(width - offset - extent, 0.0);
+        }
+        self.axis.point(offset, 0.0)
+    }
+
+    fn size_for_self(
+        &self,
+        _tree: &'static WidgetTree,
+        _index: NodeIndex,
+        ctx: LayouterSizeSelfCtx,
+    ) -> Size {
+        let Constraints { min, max } = ctx.constraints;
+        Size::new(
+            if ctx.constraints.has_bounded_width() {
+                max.width
+            } else {
+                min.width
+            },
+            if ctx.constraints.has_bounded_height() {
+                max.height
+            } else {
+                min.height
+            },
+        )
+    }
+
+    fn on_pointer(&self, tree: &'static WidgetTree, index: NodeIndex, event: PointerEvent) -> bool {
+        // Work along the axis from the first pane, whichever side that's on
+        let mut position = self.axis.main(event.position.to_vec2().to_size());
+        if self.is_mirrored(tree) {
+            position = tree.query_node_size(index).width - position;
+        }
+
+        match event.kind {
+            PointerEventKind::Down => {
+                let extents = self.pane_extents(tree, self.available_extent(tree, index));
+                let offsets = self.pane_offsets(&extents);
+
+                let divider = (0..self.panes.len().saturating_sub(1)).find(|&d| {
+                    let start = offsets[d] + extents[d];
+                    position >= start && position < start + self.divider_thickness
+                });
+                let Some(divider) = divider else {
+                    return false;
+                };
+
+                let start = offsets[divider] + extents[divider];
+                *self.dragging.borrow_mut() = Some((divider, position - start));
+                true
+            }
+            PointerEventKind::Move => {
+                let Some((divider, grab_offset)) = *self.dragging.borrow() else {
+                    return false;
+                };
+                self.drag_divider(tree, index, divider, position - grab_offset);
+                true
+            }
+            PointerEventKind::Up => self.dragging.borrow_mut().take().is_some(),
+        }
+    }
+}
+
+/// The space between two panes of a `Split`. Dragging is handled by the split itself.
+struct SplitDivider {}
+
+impl Layouter for SplitDivider {
+    fn size_for_self(
+        &self,
+        _tree: &'static WidgetTree,
+        _index: NodeIndex,
+        ctx: LayouterSizeSelfCtx,
+    ) -> Size {
+        ctx.constraints.min
+    }
+}
+
+struct SplitDividerDrawer {
+    color: Color,
+}
+
+impl Drawer for SplitDividerDrawer {
+    fn draw(&self, ctx: DrawerCtx) {
+        ctx.scene.fill(
+            vello::peniko::Fill::NonZero,
+            ctx.transform,
+            self.color,
+            None,
+            &ctx.rect,
+        );
+    }
+}
+
+/// Shifts its child down so that the child's baseline ends up `baseline` from the top, and
+/// reports that as its own baseline. A child without a baseline is treated as having one at its
+/// bottom edge.
+struct Baseline {
+    baseline: f64,
+}
+
+impl Baseline {
+    fn child_offset(&self, tree: &'static WidgetTree, child: NodeIndex) -> f64 {
+        let child_baseline = tree
+            .query_node_baseline(child)
+            .unwrap_or_else(|| tree.query_node_size(child).height);
+        self.baseline - child_baseline
+    }
+}
+
+impl Layouter for Baseline {
+    fn constraints_for_child(
+        &self,
+        _tree: &'static WidgetTree,
+        _index: NodeIndex,
+        ctx: LayouterConstrainChildrenCtx,
+    ) -> Constraints {
+        ctx.self_constraints.loosen()
+    }
+
+    fn position_for_child(
+        &self,
+        tree: &'static WidgetTree,
+        index: NodeIndex,
+        ctx: LayoutChildWasSizedCtx,
+    ) -> Point {
+        let child = tree.query_nth_child(NthChild {
+            parent_index: index,
+            child_n: ctx.child_n,
+        });
+        Point::new(0.0, self.child_offset(tree, child))
+    }
+
+    fn size_for_self(
+        &self,
+        tree: &'static WidgetTree,
+        index: NodeIndex,
+        ctx: LayouterSizeSelfCtx,
+    ) -> Size {
+        let size = match tree.children(index).first() {
+            Some(&c) => {
+                let child_size = tree.query_node_size(c);
+                Size::new(
+                    child_size.width,
+                    self.child_offset(tree, c) + child_size.height,
+                )
+            }
+            None => Size::new(0.0, self.baseline),
+        };
+        ctx.constraints.clamp_size(size)
+    }
+
+    fn baseline(&self, _tree: &'static WidgetTree, _index: NodeIndex) -> Option<f64> {
+        Some(self.baseline)
+    }
+}
+
+struct DynamicallySizedBoxLayouter {
+    size: Signal<Size>,
+}
+
+impl Layouter for DynamicallySizedBoxLayouter {
+    fn size_for_self(
+        &self,
+        tree: &'static WidgetTree,
+        _index: NodeIndex,
+        _ctx: LayouterSizeSelfCtx,
+    ) -> Size {
+        tree.get_signal(self.size)
+    }
+
+    fn constraints_for_child(
+        &self,
+        _tree: &'static WidgetTree,
+        _index: NodeIndex,
+        ctx: LayouterConstrainChildrenCtx,
+    ) -> Constraints {
+        ctx.self_constraints
+    }
+
+    fn position_for_child(
+        &self,
+        _tree: &'static WidgetTree,
+        _index: NodeIndex,
+        _ctx: LayoutChildWasSizedCtx,
+    ) -> Point {
+        Point::ORIGIN
+    }
+
+    fn min_intrinsic_width(
+        &self,
+        tree: &'static WidgetTree,
+        _index: NodeIndex,
+        _height: f64,
+    ) -> f64 {
+        tree.get_signal(self.size).width
+    }
+
+    fn max_intrinsic_width(
+        &self,
+        tree: &'static WidgetTree,
+        _index: NodeIndex,
+        _height: f64,
+    ) -> f64 {
+        tree.get_signal(self.size).width
+    }
+
+    fn min_intrinsic_height(
+        &self,
+        tree: &'static WidgetTree,
+        _index: NodeIndex,
+        _width: f64,
+    ) -> f64 {
+        tree.get_signal(self.size).height
+    }
+
+    fn max_intrinsic_height(
+        &self,
+        tree: &'static WidgetTree,
+        _index: NodeIndex,
+        _width: f64,
+    ) -> f64 {
+        tree.get_signal(self.size).height
+    }
+}
+
+struct SizedBoxLayouter {
+    size: Size,
+}
+
+impl Layouter for SizedBoxLayouter {
     fn size_for_self(
         &self,
-        tree: &'static WidgetTree,
+        _tree: &'static WidgetTree,
         _index: NodeIndex,
         _ctx: LayouterSizeSelfCtx,
     ) -> Size {
-        tree.get_signal(self.size)
+        self.size
     }
 
     fn constraints_for_child(
@@ -1160,60 +6917,53 @@ impl Layouter for DynamicallySizedBoxLayouter {
     ) -> Point {
         Point::ORIGIN
     }
-}
 
-struct SizedBoxLayouter {
-    size: Size,
-}
+    fn min_intrinsic_width(
+        &self,
+        _tree: &'static WidgetTree,
+        _index: NodeIndex,
+        _height: f64,
+    ) -> f64 {
+        self.size.width
+    }
 
-impl Layouter for SizedBoxLayouter {
-    fn size_for_self(
+    fn max_intrinsic_width(
         &self,
         _tree: &'static WidgetTree,
         _index: NodeIndex,
-        _ctx: LayouterSizeSelfCtx,
-    ) -> Size {
-        self.size
+        _height: f64,
+    ) -> f64 {
+        self.size.width
     }
 
-    fn constraints_for_child(
+    fn min_intrinsic_height(
         &self,
         _tree: &'static WidgetTree,
         _index: NodeIndex,
-        ctx: LayouterConstrainChildrenCtx,
-    ) -> Constraints {
-        ctx.self_constraints
+        _width: f64,
+    ) -> f64 {
+        self.size.height
     }
 
-    fn position_for_child(
+    fn max_intrinsic_height(
         &self,
         _tree: &'static WidgetTree,
         _index: NodeIndex,
-        _ctx: LayoutChildWasSizedCtx,
-    ) -> Point {
-        Point::ORIGIN
+        _width: f64,
+    ) -> f64 {
+        self.size.height
     }
 }
 
 // todo(chad):
 // # GENERAL
-// - Implement cache red/green algorithm
 // - Interactivity (keyboard/mouse events)
 // - Text widget
 // - Builder widgets, regenerate subtree on change
 // - Animation
 //
 // # LAYOUTERS
-// - Align
-// - AspectRatio
-// - Center
 // - Expanded
-// - FractionallySized
-// - Transform
-// - Flow
-// - Grid
-// - List
-// - Stack
 
 fn main() -> Result<()> {
     let widget_tree = WidgetTree::new();
@@ -1225,19 +6975,24 @@ fn main() -> Result<()> {
     };
     let dyn_size = widget_tree.create_signal(size);
 
-    let root = widget_tree.add_node(Box::new(RowLayouter {}), None);
+    let root = widget_tree.add_node(Box::new(RowLayouter::default()), None);
     for _ in 0..3 {
         widget_tree.add_child(
             root,
             widget_tree.add_child_return_parent(
                 DynamicallySizedBoxLayouter { size: dyn_size },
                 widget_tree.add_child_return_parent(
-                    CenteredLayouter{}, 
+                    Align {
+                        width_factor: Some(1.0),
+                        height_factor: Some(1.0),
+                        ..Align::center()
+                    },
                     (
                         SizedBoxLayouter { size },
-                        SimpleQuadDrawer {
-                            color: [0.6, 0.5, 0.4],
-                        },
+                        BoxDecoration::new()
+                            .fill(Color::new([0.6, 0.5, 0.4, 1.0]))
+                            .border(Border::all(6.0, Color::new([0.8, 0.75, 0.7, 1.0])))
+                            .radius(20.0),
                     ),
                 ),
             ),
@@ -1249,7 +7004,10 @@ fn main() -> Result<()> {
         renderers: vec![],
         state: RenderState::Suspended(None),
         scene: Scene::new(),
+        damaged_scene: Scene::new(),
         widget_tree,
+        modifiers: ModifiersState::empty(),
+        cursor_position: Point::ORIGIN,
     };
 
     let event_loop = EventLoop::new()?;
@@ -1282,3 +7040,1136 @@ fn create_vello_renderer(render_cx: &RenderContext, surface: &RenderSurface<'_>)
     )
     .expect("Couldn't create renderer")
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    fn widget_tree() -> &'static WidgetTree {
+        let widget_tree = Box::leak(Box::new(WidgetTree::new()));
+        *widget_tree.size.borrow_mut() = Size::new(400.0, 300.0);
+        widget_tree
+    }
+
+    fn sized_box(widget_tree: &'static WidgetTree, size: Size) -> NodeIndex {
+        widget_tree.add_node(Box::new(SizedBoxLayouter { size }), None)
+    }
+
+    /// Adds a node laid out by `layouter` over `children`, and makes it the root
+    fn root(
+        widget_tree: &'static WidgetTree,
+        layouter: impl Layouter + 'static,
+        children: &[NodeIndex],
+    ) -> NodeIndex {
+        let root = widget_tree.add_node(Box::new(layouter), None);
+        widget_tree.set_children(root, children);
+        *widget_tree.root.borrow_mut() = Some(root);
+        root
+    }
+
+    /// Counts how many times it's drawn
+    struct CountingDrawer(Rc<Cell<usize>>);
+
+    impl Drawer for CountingDrawer {
+        fn draw(&self, _ctx: DrawerCtx) {
+            self.0.set(self.0.get() + 1);
+        }
+    }
+
+    fn damage_bounds(widget_tree: &'static WidgetTree) -> Option<Rect> {
+        let damage = widget_tree.damage();
+        damage.rects.into_iter().reduce(|a, b| a.union(b))
+    }
+
+    #[test]
+    fn set_signal_marks_dependents_stale() {
+        let widget_tree = widget_tree();
+        let size = widget_tree.create_signal(Size::new(10.0, 10.0));
+        let root = widget_tree.add_node(Box::new(RowLayouter::default()), None);
+        let dynamic = widget_tree.add_node(Box::new(DynamicallySizedBoxLayouter { size }), None);
+        let fixed = sized_box(widget_tree, Size::new(10.0, 10.0));
+        widget_tree.set_children(root, &[fixed, dynamic]);
+        *widget_tree.root.borrow_mut() = Some(root);
+
+        assert_eq!(widget_tree.query_node_size(dynamic), Size::new(10.0, 10.0));
+        assert_eq!(widget_tree.query_node_size(root), Size::new(20.0, 10.0));
+
+        widget_tree.set_signal(size, Size::new(30.0, 20.0));
+
+        // Whatever read the signal is stale, while the sibling that didn't is left alone
+        let stale = widget_tree.stale.borrow();
+        assert!(stale.contains(&QueryDependency::NodeSize(dynamic)));
+        assert!(stale.contains(&QueryDependency::NodeSize(root)));
+        assert!(!stale.contains(&QueryDependency::NodeSize(fixed)));
+        drop(stale);
+
+        assert_eq!(widget_tree.query_node_size(dynamic), Size::new(30.0, 20.0));
+        assert_eq!(widget_tree.query_node_size(root), Size::new(40.0, 20.0));
+    }
+
+    #[test]
+    fn align_positions_and_sizes_around_child() {
+        let widget_tree = widget_tree();
+        let root = widget_tree.add_node(Box::new(Align::new(Alignment::BOTTOM_RIGHT)), None);
+        let child = sized_box(widget_tree, Size::new(100.0, 50.0));
+        widget_tree.set_children(root, &[child]);
+        *widget_tree.root.borrow_mut() = Some(root);
+
+        // Without factors it takes all the space it's given
+        assert_eq!(widget_tree.query_node_size(root), Size::new(400.0, 300.0));
+        assert_eq!(
+            widget_tree.query_node_position(child),
+            Point::new(300.0, 250.0)
+        );
+
+        let widget_tree = self::widget_tree();
+        let root = widget_tree.add_node(
+            Box::new(Align {
+                width_factor: Some(2.0),
+                height_factor: Some(1.0),
+                ..Align::center()
+            }),
+            None,
+        );
+        let child = sized_box(widget_tree, Size::new(100.0, 50.0));
+        widget_tree.set_children(root, &[child]);
+        *widget_tree.root.borrow_mut() = Some(root);
+
+        assert_eq!(widget_tree.query_node_size(root), Size::new(200.0, 50.0));
+        assert_eq!(
+            widget_tree.query_node_position(child),
+            Point::new(50.0, 0.0)
+        );
+    }
+
+    #[test]
+    fn aspect_ratio_fits_within_constraints() {
+        let widget_tree = self::widget_tree();
+        let child = sized_box(widget_tree, Size::new(10.0, 10.0));
+        let wide = root(widget_tree, AspectRatio { ratio: 2.0 }, &[child]);
+
+        assert_eq!(widget_tree.query_node_size(wide), Size::new(400.0, 200.0));
+        let constraints = widget_tree.query_node_constraints(child);
+        assert_eq!(constraints.min, Size::new(400.0, 200.0));
+        assert_eq!(constraints.max, Size::new(400.0, 200.0));
+
+        // The full width would be too tall, so the height limits it instead
+        let widget_tree = self::widget_tree();
+        let tall = root(widget_tree, AspectRatio { ratio: 0.5 }, &[]);
+        assert_eq!(widget_tree.query_node_size(tall), Size::new(150.0, 300.0));
+    }
+
+    #[test]
+    fn fractionally_sized_gives_child_a_fraction() {
+        let widget_tree = self::widget_tree();
+        let child = widget_tree.add_node(Box::new(CountingLayouter(Rc::default())), None);
+        let fraction = root(
+            widget_tree,
+            FractionallySized::new(Some(0.5), Some(0.25)),
+            &[child],
+        );
+
+        assert_eq!(widget_tree.query_node_size(child), Size::new(200.0, 75.0));
+        assert_eq!(
+            widget_tree.query_node_size(fraction),
+            Size::new(200.0, 75.0)
+        );
+
+        // Without a factor the child gets the constraints as they are
+        let widget_tree = self::widget_tree();
+        let child = widget_tree.add_node(Box::new(CountingLayouter(Rc::default())), None);
+        root(
+            widget_tree,
+            FractionallySized::new(Some(0.5), None),
+            &[child],
+        );
+        let constraints = widget_tree.query_node_constraints(child);
+        assert_eq!(constraints.min, Size::new(200.0, 0.0));
+        assert_eq!(constraints.max, Size::new(200.0, 300.0));
+    }
+
+    #[test]
+    fn stack_sizes_to_non_positioned_children() {
+        let widget_tree = self::widget_tree();
+        let large = sized_box(widget_tree, Size::new(100.0, 50.0));
+        let small = sized_box(widget_tree, Size::new(60.0, 20.0));
+        let pinned = widget_tree.add_node(
+            Box::new(Positioned {
+                left: Some(10.0),
+                right: Some(20.0),
+                bottom: Some(5.0),
+                height: Some(10.0),
+                ..Default::default()
+            }),
+            None,
+        );
+        let stack = root(
+            widget_tree,
+            Stack::new(Alignment::CENTER),
+            &[large, small, pinned],
+        );
+
+        // The positioned child doesn't count towards the stack's size
+        assert_eq!(widget_tree.query_node_size(stack), Size::new(100.0, 50.0));
+        assert_eq!(
+            widget_tree.query_node_position(small),
+            Point::new(20.0, 15.0)
+        );
+
+        // Pinned to both sides it stretches between them, and to the bottom it's measured up
+        assert_eq!(widget_tree.query_node_size(pinned), Size::new(70.0, 10.0));
+        assert_eq!(
+            widget_tree.query_node_position(pinned),
+            Point::new(10.0, 35.0)
+        );
+    }
+
+    #[test]
+    fn grid_places_and_spans_children() {
+        let widget_tree = self::widget_tree();
+        let item = |item: GridItem| widget_tree.add_node(Box::new(item), None);
+        let spanning = item(GridItem::at(0, 1).spanning(2, 1));
+        let first = item(GridItem::default());
+        let second = item(GridItem::default());
+        let grid = root(
+            widget_tree,
+            Grid {
+                implicit_row: GridTrack::Fixed(40.0),
+                column_gap: 10.0,
+                row_gap: 10.0,
+                ..Grid::new(
+                    vec![GridTrack::Fixed(100.0), GridTrack::Fraction(1.0)],
+                    vec![GridTrack::Fixed(50.0)],
+                )
+            },
+            &[spanning, first, second],
+        );
+
+        // The fraction takes what's left of the width, and the span covers both rows and the
+        // gap between them
+        assert_eq!(
+            widget_tree.query_node_position(spanning),
+            Point::new(110.0, 0.0)
+        );
+        assert_eq!(
+            widget_tree.query_node_size(spanning),
+            Size::new(290.0, 100.0)
+        );
+
+        // Auto-placed children flow around the explicitly placed one
+        assert_eq!(widget_tree.query_node_position(first), Point::ZERO);
+        assert_eq!(widget_tree.query_node_size(first), Size::new(100.0, 50.0));
+        assert_eq!(
+            widget_tree.query_node_position(second),
+            Point::new(0.0, 60.0)
+        );
+        assert_eq!(widget_tree.query_node_size(second), Size::new(100.0, 40.0));
+
+        // Placement is redone when the children change
+        let added = item(GridItem::default());
+        widget_tree.set_children(grid, &[added, spanning, first]);
+        assert_eq!(widget_tree.query_node_position(added), Point::ZERO);
+        assert_eq!(
+            widget_tree.query_node_position(first),
+            Point::new(0.0, 60.0)
+        );
+    }
+
+    #[test]
+    fn flow_wraps_children_into_runs() {
+        let widget_tree = self::widget_tree();
+        let children = [
+            Size::new(150.0, 20.0),
+            Size::new(150.0, 30.0),
+            Size::new(150.0, 10.0),
+            Size::new(100.0, 10.0),
+        ]
+        .map(|size| sized_box(widget_tree, size));
+        let flow = root(
+            widget_tree,
+            Flow {
+                spacing: 10.0,
+                run_spacing: 5.0,
+                cross_axis_alignment: CrossAxisAlignment::Center,
+                ..Flow::new(Axis::Horizontal)
+            },
+            &children,
+        );
+
+        // The third child would take the first run to 470 wide, so it starts a second one
+        let positions = children.map(|c| widget_tree.query_node_position(c));
+        assert_eq!(
+            positions,
+            [
+                Point::new(0.0, 5.0),
+                Point::new(160.0, 0.0),
+                Point::new(0.0, 35.0),
+                Point::new(160.0, 35.0),
+            ]
+        );
+        assert_eq!(widget_tree.query_node_size(flow).height, 45.0);
+    }
+
+    #[test]
+    fn scroll_view_offsets_and_clamps() {
+        let widget_tree = self::widget_tree();
+        let offset = widget_tree.create_signal(50.0);
+        let content = sized_box(widget_tree, Size::new(100.0, 1000.0));
+        let scroll = root(
+            widget_tree,
+            ScrollView::new(Axis::Vertical, offset),
+            &[content],
+        );
+
+        assert_eq!(widget_tree.query_node_size(scroll), Size::new(100.0, 300.0));
+        assert_eq!(
+            widget_tree.query_node_position(content),
+            Point::new(0.0, -50.0)
+        );
+
+        // Scrolling stops once the end of the content is in view
+        assert!(widget_tree.dispatch_scroll(Point::new(50.0, 50.0), Vec2::new(0.0, 600.0)));
+        assert_eq!(widget_tree.get_signal(offset), 650.0);
+        assert!(widget_tree.dispatch_scroll(Point::new(50.0, 50.0), Vec2::new(0.0, 100.0)));
+        assert_eq!(widget_tree.get_signal(offset), 700.0);
+        assert!(!widget_tree.dispatch_scroll(Point::new(50.0, 50.0), Vec2::new(0.0, 100.0)));
+        assert_eq!(
+            widget_tree.query_node_position(content),
+            Point::new(0.0, -700.0)
+        );
+
+        // An offset set out of range is clamped when it's used
+        widget_tree.set_signal(offset, 900.0);
+        assert_eq!(
+            widget_tree.query_node_position(content),
+            Point::new(0.0, -700.0)
+        );
+    }
+
+    #[test]
+    fn virtual_list_only_builds_items_in_view() {
+        let widget_tree = self::widget_tree();
+        let offset = widget_tree.create_signal(0.0);
+        let item_count = widget_tree.create_signal(100);
+        let built = Rc::new(Cell::new(0));
+        let list = VirtualList::new(
+            widget_tree,
+            Axis::Vertical,
+            item_count,
+            ItemExtent::Fixed(50.0),
+            offset,
+            {
+                let built = built.clone();
+                move |tree, _item| {
+                    built.set(built.get() + 1);
+                    sized_box(tree, Size::new(400.0, 50.0))
+                }
+            },
+        );
+        let list = root(widget_tree, list, &[]);
+        let item_positions = || {
+            let mut positions = widget_tree
+                .children(list)
+                .into_iter()
+                .map(|c| widget_tree.query_node_position(c).y)
+                .collect::<Vec<_>>();
+            positions.sort_by(f64::total_cmp);
+            positions
+        };
+        let expected = |items: std::ops::Range<usize>, offset: f64| {
+            items.map(|n| n as f64 * 50.0 - offset).collect::<Vec<_>>()
+        };
+
+        // Six items fill the view, plus two more after them
+        widget_tree.update();
+        assert_eq!(built.get(), 9);
+        assert_eq!(item_positions(), expected(0..9, 0.0));
+
+        // Scrolled into the middle there's overscan on both sides
+        widget_tree.set_signal(offset, 1000.0);
+        widget_tree.update();
+        assert_eq!(built.get(), 11);
+        assert_eq!(item_positions(), expected(18..29, 1000.0));
+
+        // Scrolling by an item reuses the nodes there are
+        widget_tree.set_signal(offset, 1050.0);
+        widget_tree.update();
+        assert_eq!(built.get(), 11);
+        assert_eq!(item_positions(), expected(19..30, 1050.0));
+    }
+
+    #[test]
+    fn transform_hit_tests_through_inverse() {
+        let widget_tree = self::widget_tree();
+        let transform = widget_tree.create_signal(Affine::scale(2.0));
+        let child = sized_box(widget_tree, Size::new(100.0, 100.0));
+        let transformed = root(widget_tree, Transform::from_signal(transform), &[child]);
+
+        // Scaled up around its centre, the child now reaches from -50 to 150
+        assert_eq!(
+            widget_tree.hit_test(Point::new(140.0, 140.0)),
+            vec![transformed, child]
+        );
+        assert!(widget_tree.hit_test(Point::new(160.0, 140.0)).is_empty());
+
+        widget_tree.set_signal(transform, Affine::translate((200.0, 0.0)));
+        assert_eq!(
+            widget_tree.hit_test(Point::new(250.0, 50.0)),
+            vec![transformed, child]
+        );
+    }
+
+    #[test]
+    fn root_transform_change_damages() {
+        let widget_tree = self::widget_tree();
+        let transform = widget_tree.create_signal(Affine::IDENTITY);
+        let child = widget_tree.add_node(
+            Box::new(SizedBoxLayouter {
+                size: Size::new(100.0, 100.0),
+            }),
+            Some(Box::new(BoxDecoration::new())),
+        );
+        root(widget_tree, Transform::from_signal(transform), &[child]);
+        widget_tree.draw(&mut Scene::new());
+        widget_tree.draw(&mut Scene::new());
+        assert!(widget_tree.damage().is_empty());
+
+        // Nothing above the transform reads it, but it still moves what's drawn
+        widget_tree.set_signal(transform, Affine::translate((50.0, 0.0)));
+        assert!(widget_tree.needs_repaint());
+        widget_tree.draw(&mut Scene::new());
+        assert_eq!(
+            damage_bounds(widget_tree),
+            Some(Rect::new(0.0, 0.0, 150.0, 100.0))
+        );
+    }
+
+    #[test]
+    fn scale_factor_lays_out_in_logical_units() {
+        let widget_tree = self::widget_tree();
+        let child = widget_tree.add_node(
+            Box::new(SizedBoxLayouter {
+                size: Size::new(100.0, 100.0),
+            }),
+            Some(Box::new(BoxDecoration::new())),
+        );
+        let align = root(widget_tree, Align::new(Alignment::TOP_LEFT), &[child]);
+        widget_tree.set_scale_factor(2.0);
+
+        assert_eq!(widget_tree.logical_size(), Size::new(200.0, 150.0));
+        assert_eq!(widget_tree.query_node_size(align), Size::new(200.0, 150.0));
+        assert_eq!(widget_tree.query_node_size(child), Size::new(100.0, 100.0));
+
+        // Hit testing and damage are in physical pixels
+        assert_eq!(
+            widget_tree.hit_test(Point::new(150.0, 150.0)),
+            vec![align, child]
+        );
+        assert_eq!(widget_tree.hit_test(Point::new(250.0, 150.0)), vec![align]);
+        widget_tree.draw(&mut Scene::new());
+        widget_tree.draw(&mut Scene::new());
+        widget_tree.set_scale_factor(1.5);
+        widget_tree.draw(&mut Scene::new());
+        assert_eq!(
+            damage_bounds(widget_tree),
+            Some(Rect::new(0.0, 0.0, 200.0, 200.0))
+        );
+    }
+
+    #[test]
+    fn intrinsic_width_sizes_to_content() {
+        let widget_tree = self::widget_tree();
+        let wide = sized_box(widget_tree, Size::new(100.0, 20.0));
+        let narrow = sized_box(widget_tree, Size::new(50.0, 30.0));
+        let row = widget_tree.add_node(Box::new(RowLayouter::default()), None);
+        widget_tree.set_children(row, &[wide, narrow]);
+        let align = widget_tree.add_node(Box::new(Align::center()), None);
+        widget_tree.set_children(align, &[row]);
+        root(widget_tree, IntrinsicWidth {}, &[align]);
+
+        // On its own the Align would take the whole width
+        assert_eq!(widget_tree.query_max_intrinsic_width(align, 300.0), 150.0);
+        assert_eq!(widget_tree.query_node_size(align), Size::new(150.0, 300.0));
+    }
+
+    #[test]
+    fn intrinsic_size_keys_ignore_zero_sign() {
+        let key = |extent| IntrinsicSize {
+            index: NodeIndex::new(0),
+            dimension: IntrinsicDimension::MaxWidth,
+            extent,
+        };
+        let mut keys = HashSet::new();
+        keys.insert(key(0.0));
+        keys.insert(key(-0.0));
+        keys.insert(key(f64::NAN));
+        keys.insert(key(-f64::NAN));
+        assert_eq!(keys.len(), 2);
+    }
+
+    #[test]
+    fn row_aligns_children_on_baselines() {
+        let widget_tree = self::widget_tree();
+        let with_baseline = |baseline| {
+            let text = sized_box(widget_tree, Size::new(40.0, 10.0));
+            let node = widget_tree.add_node(Box::new(Baseline { baseline }), None);
+            widget_tree.set_children(node, &[text]);
+            node
+        };
+        let low = with_baseline(30.0);
+        let high = with_baseline(10.0);
+        let row = root(
+            widget_tree,
+            RowLayouter {
+                cross_axis_alignment: CrossAxisAlignment::Baseline,
+            },
+            &[low, high],
+        );
+
+        // A child without a baseline sits on its bottom edge
+        assert_eq!(widget_tree.query_node_size(low), Size::new(40.0, 30.0));
+        assert_eq!(widget_tree.query_node_baseline(low), Some(30.0));
+
+        assert_eq!(widget_tree.query_node_position(low), Point::ZERO);
+        assert_eq!(
+            widget_tree.query_node_position(high),
+            Point::new(40.0, 20.0)
+        );
+        assert_eq!(widget_tree.query_node_size(row), Size::new(80.0, 30.0));
+        assert_eq!(widget_tree.query_node_baseline(row), Some(30.0));
+    }
+
+    #[test]
+    fn overflow_markers_follow_text_direction() {
+        let widget_tree = self::widget_tree();
+        let wide = sized_box(widget_tree, Size::new(500.0, 100.0));
+        *widget_tree.root.borrow_mut() = Some(wide);
+        widget_tree.query_node_size(wide);
+
+        let overflow = widget_tree.overflow(wide).unwrap();
+        assert_eq!(overflow.excess, Size::new(100.0, 0.0));
+
+        // Width overflows are marked on the trailing edge
+        assert_eq!(
+            overflow_marker_edges(&overflow, TextDirection::Ltr),
+            vec![Rect::new(492.0, 0.0, 500.0, 100.0)]
+        );
+        assert_eq!(
+            overflow_marker_edges(&overflow, TextDirection::Rtl),
+            vec![Rect::new(0.0, 0.0, 8.0, 100.0)]
+        );
+    }
+
+    #[test]
+    fn debug_overlay_shows_relayout_and_padding() {
+        let widget_tree = self::widget_tree();
+        let size = widget_tree.create_signal(Size::new(10.0, 10.0));
+        let dynamic = widget_tree.add_node(Box::new(DynamicallySizedBoxLayouter { size }), None);
+        let fixed = sized_box(widget_tree, Size::new(10.0, 10.0));
+        let row = root(widget_tree, RowLayouter::default(), &[fixed, dynamic]);
+        widget_tree.toggle_debug_overlay();
+        widget_tree.draw(&mut Scene::new());
+        assert!(widget_tree.recomputed.borrow().is_empty());
+
+        // Only what was laid out again since the last frame gets shaded
+        widget_tree.set_signal(size, Size::new(20.0, 10.0));
+        widget_tree.query_node_size(row);
+        let recomputed = widget_tree.recomputed.borrow().clone();
+        assert!(recomputed.contains(&dynamic));
+        assert!(recomputed.contains(&row));
+        assert!(!recomputed.contains(&fixed));
+
+        // Padding is shaded on the side it ends up on
+        let padded = Padded::directional(1.0, 2.0, 3.0, 4.0);
+        assert_eq!(
+            padded.debug_padding(widget_tree, row),
+            Insets::new(2.0, 1.0, 4.0, 3.0)
+        );
+        widget_tree.set_text_direction(TextDirection::Rtl);
+        assert_eq!(
+            padded.debug_padding(widget_tree, row),
+            Insets::new(4.0, 1.0, 2.0, 3.0)
+        );
+        assert_eq!(
+            RowLayouter::default().debug_padding(widget_tree, row),
+            Insets::ZERO
+        );
+    }
+
+    #[test]
+    fn right_to_left_mirrors_layout() {
+        let widget_tree = self::widget_tree();
+        let first = sized_box(widget_tree, Size::new(100.0, 20.0));
+        let second = sized_box(widget_tree, Size::new(50.0, 20.0));
+        let row = widget_tree.add_node(Box::new(RowLayouter::default()), None);
+        widget_tree.set_children(row, &[first, second]);
+        root(widget_tree, Align::new(Alignment::TOP_START), &[row]);
+
+        assert_eq!(widget_tree.query_node_position(row), Point::ORIGIN);
+        assert_eq!(widget_tree.query_node_position(first), Point::ORIGIN);
+        assert_eq!(
+            widget_tree.query_node_position(second),
+            Point::new(100.0, 0.0)
+        );
+
+        // The row starts from the right edge, and its children run right to left
+        widget_tree.set_text_direction(TextDirection::Rtl);
+        assert_eq!(widget_tree.query_node_position(row), Point::new(250.0, 0.0));
+        assert_eq!(
+            widget_tree.query_node_position(first),
+            Point::new(50.0, 0.0)
+        );
+        assert_eq!(widget_tree.query_node_position(second), Point::ORIGIN);
+        assert_eq!(widget_tree.query_node_size(row), Size::new(150.0, 20.0));
+    }
+
+    #[test]
+    fn table_sizes_columns_and_measures_each_separately() {
+        let widget_tree = self::widget_tree();
+        let width = widget_tree.create_signal(Size::new(120.0, 10.0));
+        let wide =
+            widget_tree.add_node(Box::new(DynamicallySizedBoxLayouter { size: width }), None);
+        let rows = [
+            vec![
+                sized_box(widget_tree, Size::new(80.0, 20.0)),
+                sized_box(widget_tree, Size::new(10.0, 10.0)),
+                sized_box(widget_tree, Size::new(10.0, 30.0)),
+            ],
+            vec![
+                sized_box(widget_tree, Size::new(30.0, 10.0)),
+                wide,
+                sized_box(widget_tree, Size::new(10.0, 10.0)),
+            ],
+        ];
+        let columns = vec![
+            TableColumn::Content,
+            TableColumn::Content,
+            TableColumn::Flex(1.0),
+        ];
+        let table = Table::new(columns).build(widget_tree, &rows);
+        *widget_tree.root.borrow_mut() = Some(table);
+
+        // Content columns fit their widest cell and the flex column takes whatever's left, while
+        // rows fit their tallest cell
+        assert_eq!(widget_tree.query_node_size(table), Size::new(400.0, 40.0));
+        assert_eq!(
+            widget_tree.query_node_constraints(rows[0][2]).max.width,
+            200.0
+        );
+        let cell = |child| widget_tree.query_node_position(widget_tree.parent(child).unwrap());
+        assert_eq!(cell(rows[0][0]), Point::new(0.0, 5.0));
+        assert_eq!(cell(rows[1][1]), Point::new(80.0, 30.0));
+        assert_eq!(cell(rows[1][2]), Point::new(200.0, 30.0));
+
+        // Changing a cell only re-measures its own column
+        widget_tree.set_signal(width, Size::new(150.0, 10.0));
+        let stale = |column| {
+            widget_tree
+                .stale
+                .borrow()
+                .contains(&QueryDependency::LayoutMeasure(LayoutMeasure {
+                    index: table,
+                    key: column,
+                }))
+        };
+        assert!(stale(1));
+        assert!(!stale(0));
+        assert_eq!(
+            widget_tree.query_node_constraints(rows[0][2]).max.width,
+            170.0
+        );
+    }
+
+    #[test]
+    fn layers_clip_and_fade_children() {
+        let widget_tree = self::widget_tree();
+        let child = widget_tree.add_node(
+            Box::new(SizedBoxLayouter {
+                size: Size::new(100.0, 100.0),
+            }),
+            Some(Box::new(BoxDecoration::new())),
+        );
+        let shifted = widget_tree.add_node(
+            Box::new(Transform::new(Affine::translate((50.0, 0.0)))),
+            None,
+        );
+        widget_tree.set_children(shifted, &[child]);
+        let clip = root(widget_tree, Clip::rounded(20.0), &[shifted]);
+
+        // The child is drawn and hit only where it overlaps the clip, minus its rounded corners
+        assert_eq!(
+            widget_tree.paint_bounds(clip),
+            Some(Rect::new(50.0, 0.0, 100.0, 100.0))
+        );
+        assert_eq!(
+            widget_tree.hit_test(Point::new(60.0, 50.0)),
+            vec![clip, shifted, child]
+        );
+        assert!(widget_tree.hit_test(Point::new(120.0, 50.0)).is_empty());
+        assert!(widget_tree.hit_test(Point::new(98.0, 2.0)).is_empty());
+
+        let widget_tree = self::widget_tree();
+        let opacity = widget_tree.create_signal(0.5);
+        let child = widget_tree.add_node(
+            Box::new(SizedBoxLayouter {
+                size: Size::new(100.0, 100.0),
+            }),
+            Some(Box::new(BoxDecoration::new())),
+        );
+        let faded = root(
+            widget_tree,
+            Opacity {
+                opacity: MaybeSignal::Signal(opacity),
+            },
+            &[child],
+        );
+        let layer = widget_tree.query_node_paint(faded).layer.clone().unwrap();
+        assert_eq!(layer.alpha, 0.5);
+        assert!(layer.clip.is_none());
+        assert_eq!(
+            widget_tree.paint_bounds(faded),
+            Some(Rect::new(0.0, 0.0, 100.0, 100.0))
+        );
+
+        // Fully transparent children don't draw anything, and fully opaque ones need no layer
+        widget_tree.set_signal(opacity, 0.0);
+        assert_eq!(widget_tree.paint_bounds(faded), None);
+        widget_tree.set_signal(opacity, 1.0);
+        assert!(widget_tree.query_node_paint(faded).layer.is_none());
+
+        let widget_tree = self::widget_tree();
+        let child = sized_box(widget_tree, Size::new(100.0, 100.0));
+        let blend = root(
+            widget_tree,
+            BlendLayer {
+                blend: Mix::Multiply.into(),
+            },
+            &[child],
+        );
+        let layer = widget_tree.query_node_paint(blend).layer.clone().unwrap();
+        assert_eq!(layer.blend, Mix::Multiply.into());
+        assert!(layer.clip.is_none());
+    }
+
+    #[test]
+    fn constraints_helpers() {
+        let constraints = |min: (f64, f64), max: (f64, f64)| Constraints {
+            min: min.into(),
+            max: max.into(),
+        };
+
+        // Pinned dimensions still have to fit
+        assert_eq!(
+            Constraints::loose(Size::new(100.0, 100.0)).tighten(Some(150.0), None),
+            constraints((100.0, 0.0), (100.0, 100.0))
+        );
+        let parent = Constraints::loose(Size::new(100.0, 80.0));
+        assert_eq!(
+            Constraints::expand(Some(50.0), None).enforce(parent),
+            Constraints::tight(Size::new(50.0, 80.0))
+        );
+        assert_eq!(
+            constraints((10.0, 10.0), (100.0, 50.0)).deflate(Insets::uniform_xy(20.0, 10.0)),
+            constraints((0.0, 0.0), (60.0, 30.0))
+        );
+        assert_eq!(
+            Constraints::tight(Size::new(100.0, 50.0)).unbounded(Axis::Vertical),
+            constraints((100.0, 0.0), (100.0, f64::INFINITY))
+        );
+        assert_eq!(
+            constraints((-5.0, f64::NAN), (3.0, 10.0)).normalize(),
+            constraints((0.0, 0.0), (3.0, 10.0))
+        );
+        assert_eq!(
+            Constraints::loose(Size::new(100.0, 100.0)).constrain_aspect(Size::new(200.0, 100.0)),
+            Size::new(100.0, 50.0)
+        );
+    }
+
+    #[test]
+    fn constraint_layout_drops_removed_items() {
+        let widget_tree = self::widget_tree();
+        let label = ConstraintAnchors::new();
+        let field = ConstraintAnchors::new();
+        let mut layout = ConstraintLayout::new();
+        let parent = layout.parent;
+        for constraint in [
+            label.left | EQ(REQUIRED) | 0.0,
+            label.right() | EQ(REQUIRED) | field.left,
+            field.right() | EQ(REQUIRED) | parent.right(),
+            label.width | EQ(REQUIRED) | field.width,
+        ] {
+            layout.add_constraint(constraint).unwrap();
+        }
+
+        let text = sized_box(widget_tree, Size::new(50.0, 20.0));
+        let label_item = widget_tree.add_node(Box::new(ConstraintItem { anchors: label }), None);
+        widget_tree.set_children(label_item, &[text]);
+        let field_item = widget_tree.add_node(Box::new(ConstraintItem { anchors: field }), None);
+        let layout = root(widget_tree, layout, &[label_item, field_item]);
+
+        // The two split the width between them
+        assert_eq!(
+            widget_tree.query_node_size(label_item),
+            Size::new(200.0, 20.0)
+        );
+        assert_eq!(
+            widget_tree.query_node_position(field_item),
+            Point::new(200.0, 0.0)
+        );
+
+        // Without the field, nothing ties the label's width to anything but its content
+        widget_tree.set_children(layout, &[label_item]);
+        assert_eq!(
+            widget_tree.query_node_size(label_item),
+            Size::new(50.0, 20.0)
+        );
+
+        widget_tree.set_children(layout, &[label_item, field_item]);
+        assert_eq!(
+            widget_tree.query_node_size(label_item),
+            Size::new(200.0, 20.0)
+        );
+    }
+
+    /// Draws a ring in its foreground, in the colour `color` is set to, and counts how many times
+    /// it's drawn
+    struct RingDrawer {
+        color: Signal<Color>,
+        drawn: Rc<Cell<usize>>,
+    }
+
+    impl Drawer for RingDrawer {
+        fn draw(&self, _ctx: DrawerCtx) {}
+
+        fn draw_foreground(&self, ctx: DrawerCtx) {
+            let color = ctx.tree.get_signal(self.color);
+            ctx.scene
+                .stroke(&Stroke::new(8.0), ctx.transform, color, None, &ctx.rect);
+            self.drawn.set(self.drawn.get() + 1);
+        }
+
+        fn paint_bounds(&self, rect: Rect) -> Rect {
+            rect.inflate(4.0, 4.0)
+        }
+    }
+
+    #[test]
+    fn foreground_is_tracked_like_background() {
+        let widget_tree = self::widget_tree();
+        let color = widget_tree.create_signal(palette::css::WHITE);
+        let drawn = Rc::new(Cell::new(0));
+        let ring = widget_tree.add_node(
+            Box::new(SizedBoxLayouter {
+                size: Size::new(100.0, 50.0),
+            }),
+            Some(Box::new(RingDrawer {
+                color,
+                drawn: drawn.clone(),
+            })),
+        );
+        *widget_tree.root.borrow_mut() = Some(ring);
+        widget_tree.draw(&mut Scene::new());
+        widget_tree.draw(&mut Scene::new());
+        assert_eq!(drawn.get(), 1);
+        assert!(widget_tree.damage().is_empty());
+        assert_eq!(
+            widget_tree.query_node_paint(ring).bounds,
+            Some(Rect::new(-4.0, -4.0, 104.0, 54.0))
+        );
+
+        // Whatever the foreground reads redraws the node, over everything it might cover
+        widget_tree.set_signal(color, palette::css::BLACK);
+        assert!(widget_tree.needs_repaint());
+        widget_tree.draw(&mut Scene::new());
+        assert_eq!(drawn.get(), 2);
+        assert_eq!(
+            damage_bounds(widget_tree),
+            Some(Rect::new(0.0, 0.0, 104.0, 54.0))
+        );
+    }
+
+    #[test]
+    fn box_decoration_bounds_and_fill() {
+        let rect = Rect::new(0.0, 0.0, 100.0, 50.0);
+        let shadow = BoxShadow {
+            color: palette::css::BLACK,
+            offset: Vec2::new(0.0, 4.0),
+            blur_radius: 8.0,
+            spread: 2.0,
+        };
+        let decoration = BoxDecoration::new().shadow(shadow).shadow(BoxShadow {
+            offset: Vec2::new(10.0, 0.0),
+            blur_radius: 0.0,
+            spread: 0.0,
+            ..shadow
+        });
+
+        // Blurs fade out within three standard deviations of the spread, offset box
+        assert_eq!(
+            decoration.paint_bounds(rect),
+            Rect::new(-14.0, -10.0, 114.0, 68.0)
+        );
+
+        // Directional gradients run from the start edge to the end edge
+        let gradient = BoxFill::Linear {
+            begin: Alignment::CENTER_START,
+            end: Alignment::CENTER_END,
+            stops: vec![(0.0, palette::css::BLACK), (1.0, palette::css::WHITE)],
+        };
+        let ends = |direction| match gradient.brush(rect, direction) {
+            Brush::Gradient(Gradient {
+                kind: vello::peniko::GradientKind::Linear { start, end },
+                ..
+            }) => (start, end),
+            brush => panic!("expected a linear gradient, got {brush:?}"),
+        };
+        assert_eq!(
+            ends(TextDirection::Ltr),
+            (Point::new(0.0, 25.0), Point::new(100.0, 25.0))
+        );
+        assert_eq!(
+            ends(TextDirection::Rtl),
+            (Point::new(100.0, 25.0), Point::new(0.0, 25.0))
+        );
+
+        // Changing the fill redraws the box and its shadows without laying anything out
+        let widget_tree = self::widget_tree();
+        let fill = widget_tree.create_signal(BoxFill::Solid(palette::css::WHITE));
+        let decorated = widget_tree.add_node(
+            Box::new(SizedBoxLayouter { size: rect.size() }),
+            Some(Box::new(decoration.fill_signal(fill))),
+        );
+        *widget_tree.root.borrow_mut() = Some(decorated);
+        widget_tree.draw(&mut Scene::new());
+        widget_tree.draw(&mut Scene::new());
+
+        widget_tree.set_signal(fill, BoxFill::Solid(palette::css::BLACK));
+        assert!(widget_tree.needs_repaint());
+        assert!(!widget_tree
+            .stale
+            .borrow()
+            .contains(&QueryDependency::NodeSize(decorated)));
+        widget_tree.draw(&mut Scene::new());
+        assert_eq!(
+            damage_bounds(widget_tree),
+            Some(Rect::new(0.0, 0.0, 114.0, 68.0))
+        );
+    }
+
+    /// Counts how many times it's visited while a frame is drawn
+    struct VisitCountingLayouter(Rc<Cell<usize>>);
+
+    impl Layouter for VisitCountingLayouter {
+        fn is_repaint_boundary(&self) -> bool {
+            self.0.set(self.0.get() + 1);
+            false
+        }
+    }
+
+    #[test]
+    fn repaint_boundaries_skip_unchanged_subtrees() {
+        let widget_tree = self::widget_tree();
+        let fill = widget_tree.create_signal(BoxFill::Solid(palette::css::RED));
+        let other_fill = widget_tree.create_signal(BoxFill::Solid(palette::css::RED));
+        let visits = Rc::new(Cell::new(0));
+        let filled = widget_tree.add_node(
+            Box::new(SizedBoxLayouter {
+                size: Size::new(10.0, 10.0),
+            }),
+            Some(Box::new(BoxDecoration::new().fill_signal(fill))),
+        );
+        let changed = widget_tree.add_child_return_parent(RepaintBoundary, filled);
+        let other_filled = widget_tree.add_node(
+            Box::new(SizedBoxLayouter {
+                size: Size::new(10.0, 10.0),
+            }),
+            Some(Box::new(BoxDecoration::new().fill_signal(other_fill))),
+        );
+        let counted = widget_tree
+            .add_child_return_parent(VisitCountingLayouter(visits.clone()), other_filled);
+        let unchanged = widget_tree.add_child_return_parent(RepaintBoundary, counted);
+        let row = root(widget_tree, RowLayouter::default(), &[changed, unchanged]);
+        widget_tree.draw(&mut Scene::new());
+        widget_tree.draw(&mut Scene::new());
+        assert_eq!(visits.get(), 1);
+
+        // The other boundary is appended as it was, without being visited
+        widget_tree.set_signal(fill, BoxFill::Solid(palette::css::BLUE));
+        widget_tree.draw(&mut Scene::new());
+        assert_eq!(visits.get(), 1);
+        assert_eq!(
+            damage_bounds(widget_tree),
+            Some(Rect::new(0.0, 0.0, 10.0, 10.0))
+        );
+
+        widget_tree.set_signal(other_fill, BoxFill::Solid(palette::css::BLUE));
+        widget_tree.draw(&mut Scene::new());
+        assert_eq!(visits.get(), 2);
+        assert_eq!(
+            damage_bounds(widget_tree),
+            Some(Rect::new(10.0, 0.0, 20.0, 10.0))
+        );
+
+        // A removed boundary is damaged where its subtree was, and forgotten
+        widget_tree.set_children(row, &[changed]);
+        widget_tree.draw(&mut Scene::new());
+        assert_eq!(
+            damage_bounds(widget_tree),
+            Some(Rect::new(10.0, 0.0, 20.0, 10.0))
+        );
+        assert!(!widget_tree.drawn.borrow().contains_key(&other_filled));
+    }
+
+    /// Counts how many times it's sized
+    struct CountingLayouter(Rc<Cell<usize>>);
+
+    impl Layouter for CountingLayouter {
+        fn size_for_self(
+            &self,
+            _tree: &'static WidgetTree,
+            _index: NodeIndex,
+            ctx: LayouterSizeSelfCtx,
+        ) -> Size {
+            self.0.set(self.0.get() + 1);
+            ctx.constraints.min
+        }
+    }
+
+    #[test]
+    fn unchanged_outputs_stop_invalidation() {
+        let widget_tree = widget_tree();
+        let size = widget_tree.create_signal(Size::new(10.0, 10.0));
+        let sized = Rc::new(Cell::new(0));
+        let root = widget_tree.add_node(Box::new(RowLayouter::default()), None);
+        let dynamic = widget_tree.add_node(Box::new(DynamicallySizedBoxLayouter { size }), None);
+        let counted = widget_tree.add_node(Box::new(CountingLayouter(sized.clone())), None);
+        widget_tree.set_children(root, &[dynamic, counted]);
+        *widget_tree.root.borrow_mut() = Some(root);
+
+        widget_tree.query_node_size(counted);
+        assert_eq!(sized.get(), 1);
+
+        // The second child's constraints depend on the first child's width, which is unchanged
+        widget_tree.set_signal(size, Size::new(10.0, 20.0));
+        assert_eq!(widget_tree.query_node_size(root), Size::new(10.0, 20.0));
+        widget_tree.query_node_size(counted);
+        assert_eq!(sized.get(), 1);
+
+        widget_tree.set_signal(size, Size::new(20.0, 20.0));
+        widget_tree.query_node_size(counted);
+        assert_eq!(sized.get(), 2);
+    }
+
+    #[test]
+    fn paint_cache_only_redraws_changed_node() {
+        let widget_tree = widget_tree();
+        let fill = widget_tree.create_signal(BoxFill::Solid(palette::css::RED));
+        let draws = Rc::new(Cell::new(0));
+        let root = widget_tree.add_node(Box::new(RowLayouter::default()), None);
+        let counted = widget_tree.add_node(
+            Box::new(SizedBoxLayouter {
+                size: Size::new(10.0, 10.0),
+            }),
+            Some(Box::new(CountingDrawer(draws.clone()))),
+        );
+        let filled = widget_tree.add_node(
+            Box::new(SizedBoxLayouter {
+                size: Size::new(10.0, 10.0),
+            }),
+            Some(Box::new(BoxDecoration::new().fill_signal(fill))),
+        );
+        widget_tree.set_children(root, &[counted, filled]);
+        *widget_tree.root.borrow_mut() = Some(root);
+
+        widget_tree.draw(&mut Scene::new());
+        widget_tree.draw(&mut Scene::new());
+        assert_eq!(draws.get(), 1);
+        assert!(!widget_tree.needs_repaint());
+
+        widget_tree.set_signal(fill, BoxFill::Solid(palette::css::BLUE));
+        assert!(widget_tree.needs_repaint());
+        let stale = widget_tree.stale.borrow();
+        assert!(stale.contains(&QueryDependency::NodePaint(filled)));
+        assert!(!stale.contains(&QueryDependency::NodePaint(counted)));
+        assert!(!stale.contains(&QueryDependency::NodePaint(root)));
+        drop(stale);
+
+        widget_tree.draw(&mut Scene::new());
+        assert_eq!(draws.get(), 1);
+        assert!(!widget_tree.needs_repaint());
+    }
+
+    #[test]
+    fn damage_rects() {
+        let widget_tree = widget_tree();
+        let fill = widget_tree.create_signal(BoxFill::Solid(palette::css::RED));
+        let size = widget_tree.create_signal(Size::new(10.0, 10.0));
+        let root = widget_tree.add_node(Box::new(RowLayouter::default()), None);
+        let dynamic = widget_tree.add_node(
+            Box::new(DynamicallySizedBoxLayouter { size }),
+            Some(Box::new(BoxDecoration::new())),
+        );
+        let filled = widget_tree.add_node(
+            Box::new(SizedBoxLayouter {
+                size: Size::new(10.0, 10.0),
+            }),
+            Some(Box::new(BoxDecoration::new().fill_signal(fill))),
+        );
+        widget_tree.set_children(root, &[dynamic, filled]);
+        *widget_tree.root.borrow_mut() = Some(root);
+
+        widget_tree.draw(&mut Scene::new());
+        assert!(!widget_tree.damage().is_empty());
+        widget_tree.draw(&mut Scene::new());
+        assert!(widget_tree.damage().is_empty());
+
+        // Only the node whose fill changed is damaged
+        widget_tree.set_signal(fill, BoxFill::Solid(palette::css::BLUE));
+        widget_tree.draw(&mut Scene::new());
+        assert_eq!(
+            damage_bounds(widget_tree),
+            Some(Rect::new(10.0, 0.0, 20.0, 10.0))
+        );
+
+        // Growing the first node moves the second along
+        widget_tree.set_signal(size, Size::new(20.0, 10.0));
+        widget_tree.draw(&mut Scene::new());
+        assert_eq!(
+            damage_bounds(widget_tree),
+            Some(Rect::new(0.0, 0.0, 30.0, 10.0))
+        );
+
+        // A removed node is damaged where it was
+        widget_tree.set_children(root, &[dynamic]);
+        widget_tree.draw(&mut Scene::new());
+        assert_eq!(
+            widget_tree.damage().rects,
+            vec![Rect::new(20.0, 0.0, 30.0, 10.0)]
+        );
+    }
+
+    #[test]
+    fn split_minimums_wider_than_available() {
+        let widget_tree = widget_tree();
+        let split = Split::new(
+            Axis::Horizontal,
+            vec![
+                SplitPane::new(widget_tree, 0.5).min(300.0),
+                SplitPane::new(widget_tree, 0.5),
+            ],
+        );
+
+        assert_eq!(split.pane_extents(widget_tree, 200.0), vec![300.0, 0.0]);
+    }
+
+    #[test]
+    fn split_needs_one_child_per_pane() {
+        let widget_tree = widget_tree();
+        let child = sized_box(widget_tree, Size::new(10.0, 10.0));
+        let split = Split::new(Axis::Horizontal, vec![SplitPane::new(widget_tree, 1.0); 2]);
+
+        assert!(split.build(widget_tree, &[child]).is_err());
+    }
+}